@@ -1,35 +1,80 @@
-mod models;
-
 use std::fmt::Write;
 
-use actix_http::KeepAlive;
 use actix_web::{
     http::{
         header::{HeaderValue, CONTENT_TYPE, SERVER},
         StatusCode,
     },
-    web, App, HttpResponse, HttpServer,
+    web, HttpResponse,
 };
 use deadpool_postgres::{Config, Pool, PoolConfig, Runtime};
 use futures::{stream::FuturesUnordered, TryStreamExt};
-use models::{Queries, Result, World};
-use rand::{prelude::SmallRng, Rng, SeedableRng};
 use tokio_postgres::{types::ToSql, NoTls};
 use yarte::ywrite_html;
 
-use crate::models::{Fortune, CONNECTION_POOL_SIZE};
+use crate::{
+    models::{Fortune, Queries, Result, World, CONNECTION_POOL_SIZE},
+    rng::random_number,
+};
+
+const WORLD_SQL: &str = "SELECT * FROM world WHERE id=$1";
+const FORTUNE_SQL: &str = "SELECT * FROM Fortune";
+
+pub struct Db {
+    pool: Pool,
+    /// `updates[n - 1]` updates `n` worlds. Built once at startup, each connection
+    /// then prepares a given size the first time it runs it.
+    updates: Vec<String>,
+}
+
+impl Db {
+    pub fn connect() -> Self {
+        let mut cfg = Config::new();
+        cfg.host = Some("tfb-database".to_string());
+        cfg.dbname = Some("hello_world".to_string());
+        cfg.user = Some("benchmarkdbuser".to_string());
+        cfg.password = Some("benchmarkdbpass".to_string());
+        let pc = PoolConfig::new(CONNECTION_POOL_SIZE);
+        cfg.pool = pc.into();
+        let pool = cfg.create_pool(Some(Runtime::Tokio1), NoTls).unwrap();
+
+        Db {
+            pool,
+            updates: (1..=500).map(update_sql).collect(),
+        }
+    }
+}
+
+fn update_sql(num: usize) -> String {
+    let mut pl = 1;
+    let mut q = String::from("UPDATE world SET randomnumber = CASE id ");
+    for _ in 1..=num {
+        write!(&mut q, "when ${} then ${} ", pl, pl + 1).unwrap();
+        pl += 2;
+    }
+    q.push_str("ELSE randomnumber END WHERE id IN (");
+    for _ in 1..=num {
+        write!(&mut q, "${},", pl).unwrap();
+        pl += 1;
+    }
+    q.pop(); // drop trailing comma
+    q.push(')');
+    q
+}
+
+/// Registers the database routes, which expect a `web::Data<Db>` in app data.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(fortune)
+        .service(db)
+        .service(queries)
+        .service(updates);
+}
 
 async fn find_random_world(pool: &Pool) -> Result<World> {
     let conn = pool.get().await?;
-    let world = conn
-        .prepare("SELECT * FROM world WHERE id=$1")
-        .await
-        .unwrap();
+    let world = conn.prepare_cached(WORLD_SQL).await?;
 
-    let mut rng = SmallRng::from_entropy();
-    let id = (rng.gen::<u32>() % 10_000 + 1) as i32;
-
-    let row = conn.query_one(&world, &[&id]).await?;
+    let row = conn.query_one(&world, &[&random_number()]).await?;
 
     Ok(World {
         id: row.get(0),
@@ -52,8 +97,8 @@ async fn find_random_worlds(pool: &Pool, num_of_worlds: usize) -> Result<Vec<Wor
 }
 
 #[actix_web::get("/db")]
-async fn db(data: web::Data<Pool>) -> Result<HttpResponse<Vec<u8>>> {
-    let world = find_random_world(&data).await?;
+async fn db(data: web::Data<Db>) -> Result<HttpResponse<Vec<u8>>> {
+    let world = find_random_world(&data.pool).await?;
     let mut bytes = Vec::with_capacity(48);
     serde_json::to_writer(&mut bytes, &world)?;
 
@@ -68,12 +113,12 @@ async fn db(data: web::Data<Pool>) -> Result<HttpResponse<Vec<u8>>> {
 
 #[actix_web::get("/queries")]
 async fn queries(
-    data: web::Data<Pool>,
+    data: web::Data<Db>,
     query: web::Query<Queries>,
 ) -> Result<HttpResponse<Vec<u8>>> {
     let n_queries = query.q;
 
-    let worlds = find_random_worlds(&data, n_queries).await?;
+    let worlds = find_random_worlds(&data.pool, n_queries).await?;
 
     let mut bytes = Vec::with_capacity(35 * n_queries);
     serde_json::to_writer(&mut bytes, &worlds)?;
@@ -89,42 +134,26 @@ async fn queries(
 
 #[actix_web::get("/updates")]
 async fn updates(
-    data: web::Data<Pool>,
+    data: web::Data<Db>,
     query: web::Query<Queries>,
 ) -> Result<HttpResponse<Vec<u8>>> {
-    let mut worlds = find_random_worlds(&data, query.q).await?;
-
-    let mut rng = SmallRng::from_entropy();
-
-    let mut updates = "UPDATE world SET randomnumber = CASE id ".to_string();
-    let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(query.q as usize * 3);
+    let mut worlds = find_random_worlds(&data.pool, query.q).await?;
 
-    let mut n_params = 1;
     for world in worlds.iter_mut() {
-        let new_random_number = (rng.gen::<u32>() % 10_000 + 1) as i32;
-        write!(&mut updates, "when ${} then ${} ", n_params, n_params + 1).unwrap();
-        world.random_number = new_random_number;
-        n_params += 2;
+        world.random_number = random_number();
     }
 
-    // need separate loop to borrow immutably
+    let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(worlds.len() * 3);
     for world in worlds.iter() {
         params.push(&world.id);
         params.push(&world.random_number);
     }
-
-    updates.push_str("ELSE randomnumber END WHERE id IN (");
     for world in worlds.iter() {
-        write!(&mut updates, "${},", n_params).unwrap();
         params.push(&world.id);
-        n_params += 1;
     }
 
-    updates.pop(); // drop trailing comma
-    updates.push(')');
-
-    let conn = data.get().await?;
-    let stmt = conn.prepare(&updates).await?;
+    let conn = data.pool.get().await?;
+    let stmt = conn.prepare_cached(&data.updates[worlds.len() - 1]).await?;
     conn.query(&stmt, &params).await?;
 
     let mut bytes = Vec::with_capacity(35 * worlds.len());
@@ -140,9 +169,9 @@ async fn updates(
 }
 
 #[actix_web::get("/fortunes")]
-async fn fortune(data: web::Data<Pool>) -> Result<HttpResponse<Vec<u8>>> {
-    let conn = data.get().await?;
-    let stmt = conn.prepare("SELECT * FROM Fortune").await?;
+async fn fortune(data: web::Data<Db>) -> Result<HttpResponse<Vec<u8>>> {
+    let conn = data.pool.get().await?;
+    let stmt = conn.prepare_cached(FORTUNE_SQL).await?;
     let params: &[&'static str] = &[];
     let s = conn.query_raw(&stmt, params).await?;
 
@@ -176,34 +205,3 @@ async fn fortune(data: web::Data<Pool>) -> Result<HttpResponse<Vec<u8>>> {
 
     Ok(res)
 }
-
-#[actix_web::main]
-async fn main() -> Result<()> {
-    println!("Starting http server: 0.0.0.0:8080");
-
-    let mut cfg = Config::new();
-    cfg.host = Some("tfb-database".to_string());
-    cfg.dbname = Some("hello_world".to_string());
-    cfg.user = Some("benchmarkdbuser".to_string());
-    cfg.password = Some("benchmarkdbpass".to_string());
-    let pc = PoolConfig::new(CONNECTION_POOL_SIZE);
-    cfg.pool = pc.into();
-    let pool = cfg.create_pool(Some(Runtime::Tokio1), NoTls).unwrap();
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
-            .service(fortune)
-            .service(db)
-            .service(queries)
-            .service(updates)
-    })
-    .keep_alive(KeepAlive::Os)
-    .client_timeout(0)
-    .backlog(1024)
-    .bind("0.0.0.0:8080")?
-    .run()
-    .await?;
-
-    Ok(())
-}
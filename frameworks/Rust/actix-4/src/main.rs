@@ -1,4 +1,11 @@
-use std::io;
+#[cfg(feature = "pg")]
+mod db_pg;
+#[cfg(feature = "pg")]
+mod models;
+#[cfg(feature = "pg")]
+mod rng;
+
+use std::{io, time::Duration};
 
 use actix_http::KeepAlive;
 use actix_web::{
@@ -55,17 +62,36 @@ async fn plaintext() -> HttpResponse<Bytes> {
     res
 }
 
+/// Registers the Postgres routes, all workers sharing one pool.
+#[cfg(feature = "pg")]
+fn db_routes() -> impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static {
+    let db = web::Data::new(db_pg::Db::connect());
+    move |cfg| {
+        cfg.app_data(db.clone());
+        db_pg::routes(cfg);
+    }
+}
+
+/// Without the `pg` feature there are no database routes.
+#[cfg(not(feature = "pg"))]
+fn db_routes() -> impl Fn(&mut web::ServiceConfig) + Clone + Send + 'static {
+    |_| {}
+}
+
 #[actix_web::main]
 async fn main() -> Result<()> {
     println!("Started http server: 127.0.0.1:8080");
 
+    let db_routes = db_routes();
+
     HttpServer::new(move || {
         App::new()
             .service(web::resource("/json").to(json))
             .service(web::resource("/plaintext").to(plaintext))
+            .configure(&db_routes)
     })
     .keep_alive(KeepAlive::Os)
-    .client_timeout(0)
+    .client_request_timeout(Duration::ZERO)
     .backlog(1024)
     .bind("0.0.0.0:8080")?
     .run()
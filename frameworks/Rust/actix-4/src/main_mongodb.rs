@@ -1,6 +1,10 @@
 mod models;
+mod rng;
 
 use models::{Fortune, Queries, Result, World, CONNECTION_POOL_SIZE};
+use rng::random_number;
+
+use std::time::Duration;
 
 use actix_http::{
     header::{HeaderValue, CONTENT_TYPE, SERVER},
@@ -11,7 +15,6 @@ use anyhow::bail;
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use mongodb::bson::doc;
 use mongodb::{options::ClientOptions, Client};
-use rand::{prelude::SmallRng, Rng, SeedableRng};
 use tokio::runtime::Handle;
 use yarte::ywrite_html;
 
@@ -24,8 +27,7 @@ async fn find_random_world(data: web::Data<Data>) -> Result<World> {
     let runtime = data.tokio_runtime.clone();
     runtime
         .spawn(async move {
-            let mut rng = SmallRng::from_entropy();
-            let id = (rng.gen::<u32>() % 10_000 + 1) as i32;
+            let id = random_number();
 
             let coll = data.client.database("hello_world").collection("world");
             let world = coll
@@ -97,10 +99,9 @@ async fn updates(
 
     let mut worlds = find_random_worlds(data, query.q).await?;
 
-    let mut rng = SmallRng::from_entropy();
     let mut updates = Vec::new();
     for world in worlds.iter_mut() {
-        let new_random_number = (rng.gen::<u32>() % 10_000 + 1) as i32;
+        let new_random_number = random_number();
         updates.push(doc! {
             "q": { "id": world.id }, "u": { "$set": { "randomNumber": new_random_number }}
         });
@@ -205,7 +206,7 @@ async fn async_main() -> Result<()> {
             .service(updates)
     })
     .keep_alive(KeepAlive::Os)
-    .client_timeout(0)
+    .client_request_timeout(Duration::ZERO)
     .backlog(1024)
     .bind("0.0.0.0:8080")?
     .run()
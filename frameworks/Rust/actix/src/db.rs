@@ -4,10 +4,10 @@ use std::io;
 
 use actix::prelude::*;
 use diesel::prelude::*;
-use diesel::result::Error;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
+use crate::db_update::update_worlds;
 use crate::models;
 
 pub struct DbExecutor {
@@ -106,15 +106,9 @@ impl Handler<UpdateWorld> for DbExecutor {
         }
         worlds.sort_by_key(|w| w.id);
 
-        let _ = self.conn.transaction::<(), Error, _>(|| {
-            for w in &worlds {
-                let _ = diesel::update(world)
-                    .filter(id.eq(w.id))
-                    .set(randomnumber.eq(w.randomnumber))
-                    .execute(&self.conn);
-            }
-            Ok(())
-        });
+        if update_worlds(&self.conn, &worlds).is_err() {
+            return Err(io::Error::new(io::ErrorKind::Other, "Database error"));
+        }
 
         Ok(worlds)
     }
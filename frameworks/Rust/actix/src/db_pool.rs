@@ -0,0 +1,121 @@
+//! Diesel on an r2d2 pool, queried from actix's blocking thread pool
+
+use std::cell::RefCell;
+use std::{fmt, io};
+
+use actix_web::error::BlockingError;
+use actix_web::web;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::db_update::update_worlds;
+use crate::models;
+
+type Conn = PooledConnection<ConnectionManager<PgConnection>>;
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+fn random_id() -> i32 {
+    RNG.with(|rng| rng.borrow_mut().gen_range(1, 10_001))
+}
+
+fn db_error<E: fmt::Debug>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", e))
+}
+
+#[derive(Clone)]
+pub struct DieselPool(Pool<ConnectionManager<PgConnection>>);
+
+impl DieselPool {
+    /// Opens `size` connections up front. Every blocking task holds one
+    /// connection for its whole run, so the blocking thread pool should be
+    /// at least as large.
+    pub fn connect(db_url: &str, size: u32) -> io::Result<DieselPool> {
+        let pool = Pool::builder()
+            .max_size(size)
+            .min_idle(Some(size))
+            .build(ConnectionManager::new(db_url))
+            .map_err(db_error)?;
+
+        Ok(DieselPool(pool))
+    }
+
+    /// Checks out a connection and runs `f` with it on a blocking thread
+    async fn run<F, T>(&self, f: F) -> io::Result<T>
+    where
+        F: FnOnce(&Conn) -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.0.clone();
+        web::block(move || {
+            let conn = pool.get().map_err(db_error)?;
+            f(&conn)
+        })
+        .await
+        .map_err(|e| match e {
+            BlockingError::Error(e) => e,
+            BlockingError::Canceled => db_error("blocking task canceled"),
+        })
+    }
+
+    pub async fn get_world(&self) -> io::Result<models::World> {
+        self.run(|conn| {
+            use crate::schema::world::dsl::*;
+
+            world.find(random_id()).first(&**conn).map_err(db_error)
+        })
+        .await
+    }
+
+    pub async fn get_worlds(&self, num: u16) -> io::Result<Vec<models::World>> {
+        self.run(move |conn| {
+            use crate::schema::world::dsl::*;
+
+            (0..num)
+                .map(|_| world.find(random_id()).first(&**conn).map_err(db_error))
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn update(&self, num: u16) -> io::Result<Vec<models::World>> {
+        self.run(move |conn| {
+            use crate::schema::world::dsl::*;
+
+            let mut worlds = (0..num)
+                .map(|_| {
+                    let mut w: models::World =
+                        world.find(random_id()).first(&**conn).map_err(db_error)?;
+                    w.randomnumber = random_id();
+                    Ok(w)
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            worlds.sort_by_key(|w| w.id);
+
+            update_worlds(conn, &worlds).map_err(db_error)?;
+
+            Ok(worlds)
+        })
+        .await
+    }
+
+    pub async fn tell_fortune(&self) -> io::Result<Vec<models::Fortune>> {
+        self.run(|conn| {
+            use crate::schema::fortune::dsl::*;
+
+            let mut items =
+                fortune.load::<models::Fortune>(&**conn).map_err(db_error)?;
+            items.push(models::Fortune {
+                id: 0,
+                message: "Additional fortune added at request time.".to_string(),
+            });
+            items.sort_by(|it, next| it.message.cmp(&next.message));
+            Ok(items)
+        })
+        .await
+    }
+}
@@ -0,0 +1,51 @@
+//! Writing updated worlds back, shared by the diesel variants
+
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer};
+
+use crate::models;
+
+/// Writes the new `randomnumber` of every world back to the database.
+pub fn update_worlds(conn: &PgConnection, worlds: &[models::World]) -> QueryResult<()> {
+    let mut changes: Vec<(i32, i32)> =
+        worlds.iter().map(|w| (w.id, w.randomnumber)).collect();
+    changes.sort_by_key(|&(id, _)| id);
+    changes.dedup_by_key(|&mut (id, _)| id);
+
+    if cfg!(feature = "per-row-updates") {
+        update_worlds_per_row(conn, &changes)
+    } else {
+        update_worlds_batch(conn, &changes)
+    }
+}
+
+fn update_worlds_batch(conn: &PgConnection, changes: &[(i32, i32)]) -> QueryResult<()> {
+    let (ids, numbers): (Vec<i32>, Vec<i32>) = changes.iter().copied().unzip();
+
+    diesel::sql_query(
+        "UPDATE world SET randomnumber = v.randomnumber \
+         FROM UNNEST($1::int[], $2::int[]) AS v (id, randomnumber) \
+         WHERE world.id = v.id",
+    )
+    .bind::<Array<Integer>, _>(ids)
+    .bind::<Array<Integer>, _>(numbers)
+    .execute(conn)?;
+    Ok(())
+}
+
+fn update_worlds_per_row(
+    conn: &PgConnection,
+    changes: &[(i32, i32)],
+) -> QueryResult<()> {
+    use crate::schema::world::dsl::*;
+
+    conn.transaction(|| {
+        for &(wid, number) in changes {
+            diesel::update(world)
+                .filter(id.eq(wid))
+                .set(randomnumber.eq(number))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
@@ -11,6 +11,7 @@ use askama::Template;
 use bytes::BytesMut;
 
 mod db;
+mod db_update;
 mod models;
 mod schema;
 mod utils;
@@ -7,6 +7,7 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use actix_codec::{AsyncRead, AsyncWrite, Decoder};
+use actix_http::http::{Method, Version};
 use actix_http::{h1, Request};
 use actix_rt::net::TcpStream;
 use actix_server::Server;
@@ -21,9 +22,13 @@ use crate::utils::Writer;
 
 const JSON: &[u8] = b"HTTP/1.1 200 OK\r\nServer: A\r\nContent-Type: application/json\r\nContent-Length: 27\r\n";
 const PLAIN: &[u8] = b"HTTP/1.1 200 OK\r\nServer: A\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n";
-const HTTPNFOUND: &[u8] = b"HTTP/1.1 400 OK\r\n";
-const HDR_SERVER: &[u8] = b"Server: A\r\n";
+const HTTPNFOUND: &[u8] = b"HTTP/1.1 404 Not Found\r\nServer: A\r\nContent-Type: text/plain\r\nContent-Length: 9\r\n";
+const HTTPNALLOWED: &[u8] = b"HTTP/1.1 405 Method Not Allowed\r\nServer: A\r\nAllow: GET, HEAD\r\nContent-Type: text/plain\r\nContent-Length: 18\r\n";
+const HDR_CLOSE: &[u8] = b"Connection: close\r\n";
+const HDR_KEEPALIVE: &[u8] = b"Connection: keep-alive\r\n";
 const BODY: &[u8] = b"Hello, World!";
+const BODY_NFOUND: &[u8] = b"Not Found";
+const BODY_NALLOWED: &[u8] = b"Method Not Allowed";
 
 #[derive(Serialize)]
 pub struct Message {
@@ -35,32 +40,67 @@ struct App {
     read_buf: BytesMut,
     write_buf: BytesMut,
     codec: h1::Codec,
+    closing: bool,
+    /// The peer shut down its write half; answer what it sent, then close.
+    read_closed: bool,
 }
 
 impl App {
     fn handle_request(&mut self, req: Request) {
+        let head = req.head();
+        let head_only = head.method == Method::HEAD;
+        let allowed = head_only || head.method == Method::GET;
+
+        // the codec already applied the HTTP/1.0 and `Connection` header rules
+        let hdr_conn = if !self.codec.keepalive() {
+            self.closing = true;
+            Some(HDR_CLOSE)
+        } else if head.version == Version::HTTP_10 {
+            Some(HDR_KEEPALIVE)
+        } else {
+            None
+        };
+
         match req.path() {
-            "/json" => {
-                let message = Message {
-                    message: "Hello, World!",
-                };
-                self.write_buf.put_slice(JSON);
-                self.codec.config().set_date(&mut self.write_buf);
-                message
-                    .json_write(&mut Writer(&mut self.write_buf))
-                    .unwrap();
+            "/json" if allowed => {
+                self.write_head(JSON, hdr_conn);
+                if !head_only {
+                    let message = Message {
+                        message: "Hello, World!",
+                    };
+                    message
+                        .json_write(&mut Writer(&mut self.write_buf))
+                        .unwrap();
+                }
             }
-            "/plaintext" => {
-                self.write_buf.put_slice(PLAIN);
-                self.codec.config().set_date(&mut self.write_buf);
-                self.write_buf.put_slice(BODY);
+            "/plaintext" if allowed => {
+                self.write_head(PLAIN, hdr_conn);
+                if !head_only {
+                    self.write_buf.put_slice(BODY);
+                }
+            }
+            "/json" | "/plaintext" => {
+                self.write_head(HTTPNALLOWED, hdr_conn);
+                self.write_buf.put_slice(BODY_NALLOWED);
             }
             _ => {
-                self.write_buf.put_slice(HTTPNFOUND);
-                self.write_buf.put_slice(HDR_SERVER);
+                self.write_head(HTTPNFOUND, hdr_conn);
+                if !head_only {
+                    self.write_buf.put_slice(BODY_NFOUND);
+                }
             }
         }
     }
+
+    /// Writes the status line and headers, which must not include `Date`.
+    #[inline]
+    fn write_head(&mut self, hdrs: &[u8], hdr_conn: Option<&[u8]>) {
+        self.write_buf.put_slice(hdrs);
+        if let Some(hdr_conn) = hdr_conn {
+            self.write_buf.put_slice(hdr_conn);
+        }
+        self.codec.config().set_date(&mut self.write_buf);
+    }
 }
 
 impl Future for App {
@@ -69,7 +109,7 @@ impl Future for App {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
 
-        loop {
+        while !this.read_closed {
             if this.read_buf.capacity() - this.read_buf.len() < 512 {
                 this.read_buf.reserve(32_768);
             }
@@ -78,7 +118,7 @@ impl Future for App {
                 Poll::Pending => break,
                 Poll::Ready(Ok(n)) => {
                     if n == 0 {
-                        return Poll::Ready(Ok(()));
+                        this.read_closed = true;
                     }
                 }
                 Poll::Ready(Err(_)) => return Poll::Ready(Err(())),
@@ -89,9 +129,12 @@ impl Future for App {
             this.write_buf.reserve(32_768);
         }
 
-        loop {
+        // nothing after a `Connection: close` request gets an answer
+        while !this.closing {
             match this.codec.decode(&mut Pin::new(&mut this.read_buf)) {
                 Ok(Some(h1::Message::Item(req))) => this.handle_request(req),
+                // request bodies are not used by any route
+                Ok(Some(h1::Message::Chunk(_))) => (),
                 Ok(None) => break,
                 _ => return Poll::Ready(Err(())),
             }
@@ -121,6 +164,11 @@ impl Future for App {
                 this.write_buf.advance(written);
             }
         }
+
+        // everything is flushed, dropping the stream closes the connection
+        if (this.closing || this.read_closed) && this.write_buf.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
         Poll::Pending
     }
 }
@@ -138,6 +186,8 @@ async fn main() -> io::Result<()> {
                 read_buf: BytesMut::with_capacity(32_768),
                 write_buf: BytesMut::with_capacity(32_768),
                 codec: h1::Codec::default(),
+                closing: false,
+                read_closed: false,
             })
         })?
         .start()
@@ -7,6 +7,7 @@ mod models_common;
 mod models_bb8;
 mod database_bb8;
 mod utils;
+mod rng;
 mod server;
 mod common;
 
@@ -24,18 +25,16 @@ use axum::http::{header, HeaderValue};
 use bb8_postgres::tokio_postgres::{Row, Statement};
 use tower_http::set_header::SetResponseHeaderLayer;
 use hyper::Body;
-use rand::rngs::SmallRng;
-use rand::{SeedableRng};
 use tokio_pg_mapper::FromTokioPostgresRow;
 use yarte::Template;
 
 use models_bb8::{World, Fortune};
-use utils::{Params, parse_params, random_number};
+use rng::random_number;
+use utils::{Params, parse_params};
 use crate::utils::Utf8Html;
 
 async fn db(DatabaseConnection(conn): DatabaseConnection) -> impl IntoResponse {
-    let mut rng = SmallRng::from_entropy();
-    let number = random_number(&mut rng);
+    let number = random_number();
 
     let select = prepare_fetch_world_by_id_statement(&conn).await;
     let world = fetch_world_by_id_using_statement(&conn, number, &select).await;
@@ -52,14 +51,12 @@ async fn fetch_world_by_id_using_statement(conn: &Connection, number: i32, selec
 async fn queries(DatabaseConnection(conn): DatabaseConnection, Query(params): Query<Params>) -> impl IntoResponse {
     let q = parse_params(params);
 
-    let mut rng = SmallRng::from_entropy();
-
     let mut results = Vec::with_capacity(q as usize);
 
     let select = prepare_fetch_world_by_id_statement(&conn).await;
 
     for _ in 0..q {
-        let query_id = random_number(&mut rng);
+        let query_id = random_number();
 
         let result :World = fetch_world_by_id_using_statement(&conn, query_id, &select).await;
 
@@ -99,17 +96,15 @@ async fn fortunes(DatabaseConnection(conn): DatabaseConnection) -> impl IntoResp
 async fn updates(DatabaseConnection(conn): DatabaseConnection, Query(params): Query<Params>) -> impl IntoResponse {
     let q = parse_params(params);
 
-    let mut rng = SmallRng::from_entropy();
-
     let mut results = Vec::with_capacity(q as usize);
 
     let select = prepare_fetch_world_by_id_statement(&conn).await;
 
     for _ in 0..q {
-        let query_id = random_number(&mut rng);
+        let query_id = random_number();
         let mut result :World = fetch_world_by_id_using_statement(&conn, query_id, &select).await;
 
-        result.randomnumber = random_number(&mut rng);
+        result.randomnumber = random_number();
         results.push(result);
     }
 
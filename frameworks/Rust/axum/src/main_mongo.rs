@@ -7,6 +7,7 @@ mod models_common;
 mod models_mongo;
 mod database_mongo;
 mod utils;
+mod rng;
 mod server;
 mod common;
 
@@ -24,20 +25,18 @@ use axum::http::{header, HeaderValue};
 use futures::stream::StreamExt;
 use tower_http::set_header::SetResponseHeaderLayer;
 use hyper::Body;
-use rand::rngs::SmallRng;
-use rand::{SeedableRng};
 use yarte::Template;
 use mongodb::{bson::doc, Client, Database};
 use mongodb::options::ClientOptions;
 
 use models_mongo::{World, Fortune};
-use utils::{Params, parse_params, random_number, Utf8Html};
+use rng::random_number;
+use utils::{Params, parse_params, Utf8Html};
 use database_mongo::DatabaseConnection;
 use models_mongo::FortuneInfo;
 
 async fn db(DatabaseConnection(mut db): DatabaseConnection) -> impl IntoResponse {
-    let mut rng = SmallRng::from_entropy();
-    let number = random_number(&mut rng);
+    let number = random_number();
 
     let world = find_world_by_id(&mut db, number).await;
 
@@ -56,12 +55,10 @@ async fn find_world_by_id(db: &mut Database, number: i32) -> World {
 async fn queries(DatabaseConnection(mut db): DatabaseConnection, Query(params): Query<Params>) -> impl IntoResponse {
     let q = parse_params(params);
 
-    let mut rng = SmallRng::from_entropy();
-
     let mut results = Vec::with_capacity(q as usize);
 
     for _ in 0..q {
-        let query_id = random_number(&mut rng);
+        let query_id = random_number();
 
         let result :World =  find_world_by_id(&mut db, query_id).await;
 
@@ -7,6 +7,7 @@ mod models_common;
 mod models_sqlx;
 mod database_sqlx;
 mod utils;
+mod rng;
 mod server;
 mod common;
 
@@ -23,18 +24,16 @@ use axum::{
 use axum::http::{header, HeaderValue};
 use tower_http::set_header::SetResponseHeaderLayer;
 use hyper::Body;
-use rand::rngs::SmallRng;
-use rand::{SeedableRng};
 use sqlx::PgPool;
 use yarte::Template;
 
 use models_sqlx::{World, Fortune};
 use database_sqlx::create_pool;
-use utils::{Params, parse_params, random_number, Utf8Html};
+use rng::random_number;
+use utils::{Params, parse_params, Utf8Html};
 
 async fn db(DatabaseConnection(mut conn): DatabaseConnection) -> impl IntoResponse {
-    let mut rng = SmallRng::from_entropy();
-    let number = random_number(&mut rng);
+    let number = random_number();
 
     let world : World = sqlx::query_as("SELECT id, randomnumber FROM World WHERE id = $1").bind(number)
         .fetch_one(&mut conn).await.ok().expect("error loading world");
@@ -45,12 +44,10 @@ async fn db(DatabaseConnection(mut conn): DatabaseConnection) -> impl IntoRespon
 async fn queries(DatabaseConnection(mut conn): DatabaseConnection, Query(params): Query<Params>) -> impl IntoResponse {
     let q = parse_params(params);
 
-    let mut rng = SmallRng::from_entropy();
-
     let mut results = Vec::with_capacity(q as usize);
 
     for _ in 0..q {
-        let query_id = random_number(&mut rng);
+        let query_id = random_number();
 
         let result :World =  sqlx::query_as("SELECT * FROM World WHERE id = $1").bind(query_id)
             .fetch_one(&mut conn).await.ok().expect("error loading world");
@@ -84,16 +81,14 @@ async fn fortunes(DatabaseConnection(mut conn): DatabaseConnection) -> impl Into
 async fn updates(DatabaseConnection(mut conn): DatabaseConnection, Query(params): Query<Params>) -> impl IntoResponse {
     let q = parse_params(params);
 
-    let mut rng = SmallRng::from_entropy();
-
     let mut results = Vec::with_capacity(q as usize);
 
     for _ in 0..q {
-        let query_id = random_number(&mut rng);
+        let query_id = random_number();
         let mut result :World =  sqlx::query_as("SELECT * FROM World WHERE id = $1").bind(query_id)
             .fetch_one(&mut conn).await.ok().expect("error loading world");
 
-        result.random_number = random_number(&mut rng);
+        result.random_number = random_number();
         results.push(result);
     }
 
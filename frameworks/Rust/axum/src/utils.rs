@@ -2,8 +2,6 @@ use std::convert::Infallible;
 use axum::body::{Bytes, Full};
 use axum::http::{header, HeaderValue, Response, StatusCode};
 use axum::response::IntoResponse;
-use rand::Rng;
-use rand::rngs::SmallRng;
 use serde::{Deserialize};
 
 #[derive(Debug, Deserialize)]
@@ -11,10 +9,6 @@ pub struct Params {
     queries: Option<String>,
 }
 
-pub fn random_number(rng: &mut SmallRng) -> i32 {
-    (rng.gen::<u32>() % 10_000 + 1) as i32
-}
-
 pub fn parse_params(params: Params) -> i32 {
     let mut q = 0;
 
@@ -0,0 +1,125 @@
+//! A tiny JSON serializer that writes straight into a raw response buffer.
+//!
+//! Nothing here allocates. The `*_len` functions are `const` so that callers can size
+//! headers (e.g. `Content-Length`) before a single byte of the body is written.
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Number of bytes `s` takes once quoted and escaped as a JSON string
+pub const fn string_len(s: &[u8]) -> usize {
+   let mut len = 2;
+   let mut i = 0;
+   while i < s.len() {
+      len += match s[i] {
+         b'"' | b'\\' | b'\n' | b'\r' | b'\t' | 0x08 | 0x0c => 2,
+         0x00..=0x1f => 6,
+         _ => 1,
+      };
+      i += 1;
+   }
+   len
+}
+
+/// Number of bytes `{"<key>":"<value>"}` takes once serialized
+pub const fn object_len(key: &[u8], value: &[u8]) -> usize {
+   string_len(key) + string_len(value) + 3
+}
+
+/// Number of decimal digits needed to print `n`
+pub const fn usize_len(mut n: usize) -> usize {
+   let mut len = 1;
+   while n >= 10 {
+      n /= 10;
+      len += 1;
+   }
+   len
+}
+
+pub struct JsonWriter {
+   buf: *mut u8,
+   len: usize,
+}
+
+impl JsonWriter {
+   /// The caller guarantees `buf` has room for everything that will be written
+   #[inline]
+   pub unsafe fn new(buf: *mut u8) -> Self {
+      Self { buf, len: 0 }
+   }
+
+   /// Bytes written so far
+   #[inline]
+   pub fn written(&self) -> usize {
+      self.len
+   }
+
+   #[inline]
+   pub unsafe fn raw(&mut self, bytes: &[u8]) {
+      core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.buf.add(self.len), bytes.len());
+      self.len += bytes.len();
+   }
+
+   #[inline]
+   unsafe fn byte(&mut self, b: u8) {
+      *self.buf.add(self.len) = b;
+      self.len += 1;
+   }
+
+   /// Writes `s` as a quoted, escaped JSON string
+   pub unsafe fn string(&mut self, s: &[u8]) {
+      self.byte(b'"');
+
+      // Copy unescaped runs in one go and only stop for bytes that need escaping
+      let mut start = 0;
+      for (i, &b) in s.iter().enumerate() {
+         let escape = match b {
+            b'"' => Some(*b"\\\""),
+            b'\\' => Some(*b"\\\\"),
+            b'\n' => Some(*b"\\n"),
+            b'\r' => Some(*b"\\r"),
+            b'\t' => Some(*b"\\t"),
+            0x08 => Some(*b"\\b"),
+            0x0c => Some(*b"\\f"),
+            0x00..=0x1f => None,
+            _ => continue,
+         };
+
+         self.raw(&s[start..i]);
+         match escape {
+            Some(escape) => self.raw(&escape),
+            None => {
+               self.raw(b"\\u00");
+               self.byte(HEX[(b >> 4) as usize]);
+               self.byte(HEX[(b & 0xf) as usize]);
+            }
+         }
+         start = i + 1;
+      }
+      self.raw(&s[start..]);
+
+      self.byte(b'"');
+   }
+
+   /// Writes a single-member object, `{"<key>":"<value>"}`
+   #[inline]
+   pub unsafe fn object(&mut self, key: &[u8], value: &[u8]) {
+      self.byte(b'{');
+      self.string(key);
+      self.byte(b':');
+      self.string(value);
+      self.byte(b'}');
+   }
+
+   /// Writes `n` in decimal, as used for `Content-Length`
+   #[inline]
+   pub unsafe fn usize(&mut self, mut n: usize) {
+      let digits = usize_len(n);
+      let mut i = digits;
+      while i > 0 {
+         i -= 1;
+         *self.buf.add(self.len + i) = b'0' + (n % 10) as u8;
+         n /= 10;
+      }
+      self.len += digits;
+   }
+}
@@ -7,32 +7,48 @@ use faf::const_concat_bytes;
 use faf::const_http::*;
 use faf::util::{const_len, memcmp};
 
-const ROUTE_PLAINTEXT: &[u8] = b"/plaintext";
-const ROUTE_PLAINTEXT_LEN: usize = const_len(ROUTE_PLAINTEXT);
+mod json;
+mod routes;
+
+use json::JsonWriter;
+use routes::{Response, Route, CONTENT_LENGTH};
+
+const DEFAULT_PORT: u16 = 8080;
 
 const TEXT_PLAIN_CONTENT_TYPE: &[u8] = b"Content-Type: text/plain";
-const CONTENT_LENGTH: &[u8] = b"Content-Length: ";
+const PLAINTEXT_HEAD: &[u8] = static_head!(TEXT_PLAIN_CONTENT_TYPE);
 const PLAINTEXT_BODY: &[u8] = b"Hello, World!";
-const PLAINTEXT_BODY_LEN: usize = const_len(PLAINTEXT_BODY);
-const PLAINTEXT_BODY_SIZE: &[u8] = b"13";
 
-const PLAINTEXT_BASE: &[u8] = const_concat_bytes!(
-   HTTP_200_OK,
-   CRLF,
-   SERVER,
-   CRLF,
-   TEXT_PLAIN_CONTENT_TYPE,
-   CRLF,
-   CONTENT_LENGTH,
-   PLAINTEXT_BODY_SIZE,
-   CRLF
-);
+const APPLICATION_JSON_CONTENT_TYPE: &[u8] = b"Content-Type: application/json";
+const JSON_KEY: &[u8] = b"message";
+const JSON_VALUE: &[u8] = b"Hello, World!";
+const JSON_BODY_LEN: usize = json::object_len(JSON_KEY, JSON_VALUE);
+
+const JSON_BASE: &[u8] =
+   const_concat_bytes!(HTTP_200_OK, CRLF, SERVER, CRLF, APPLICATION_JSON_CONTENT_TYPE, CRLF, CONTENT_LENGTH);
 
-const PLAINTEXT_BASE_LEN: usize = const_len(PLAINTEXT_BASE);
+/// Matched in order, so keep the hottest route first
+const ROUTES: &[Route] = &[
+   Route::new(GET, b"/plaintext", Response::Static { head: PLAINTEXT_HEAD, body: PLAINTEXT_BODY }),
+   Route::new(GET, b"/json", Response::Handler(json)),
+];
 
 const PLAINTEXT_TEST: &[u8] = b"HTTP/1.1 200 OK\r\nServer: F\r\nContent-Type: text/plain\r\nContent-Length: 13\r\nDate: Thu, 18 Nov 2021 23:15:07 GMT\r\n\r\nHello, World!";
 const PLAINTEXT_TEST_LEN: usize = const_len(PLAINTEXT_TEST);
 
+unsafe fn json(response_buffer: *mut u8, date_buff: *const u8) -> usize {
+   // The body length is known at compile time, so the headers go out first and the
+   // body is serialized right behind them with no intermediate buffer
+   let mut w = JsonWriter::new(response_buffer);
+   w.raw(JSON_BASE);
+   w.usize(JSON_BODY_LEN);
+   w.raw(CRLF);
+   w.raw(core::slice::from_raw_parts(date_buff, DATE_LEN));
+   w.raw(CRLFCRLF);
+   w.object(JSON_KEY, JSON_VALUE);
+   w.written()
+}
+
 #[inline]
 fn cb(
    method: *const u8,
@@ -42,38 +58,16 @@ fn cb(
    response_buffer: *mut u8,
    date_buff: *const u8,
 ) -> usize {
-   unsafe {
-      if likely(method_len >= GET_LEN && path_len >= ROUTE_PLAINTEXT_LEN) {
-         if likely(memcmp(GET.as_ptr(), method, GET_LEN) == 0) {
-            if likely(memcmp(ROUTE_PLAINTEXT.as_ptr(), path, ROUTE_PLAINTEXT_LEN) == 0) {
-               core::ptr::copy_nonoverlapping(PLAINTEXT_BASE.as_ptr(), response_buffer, PLAINTEXT_BASE_LEN);
-               core::ptr::copy_nonoverlapping(date_buff, response_buffer.add(PLAINTEXT_BASE_LEN), DATE_LEN);
-               core::ptr::copy_nonoverlapping(
-                  CRLFCRLF.as_ptr(),
-                  response_buffer.add(PLAINTEXT_BASE_LEN + DATE_LEN),
-                  CRLFCRLF_LEN,
-               );
-               core::ptr::copy_nonoverlapping(
-                  PLAINTEXT_BODY.as_ptr(),
-                  response_buffer.add(PLAINTEXT_BASE_LEN + DATE_LEN + CRLFCRLF_LEN),
-                  PLAINTEXT_BODY_LEN,
-               );
+   unsafe { routes::dispatch(ROUTES, method, method_len, path, path_len, response_buffer, date_buff) }
+}
 
-               PLAINTEXT_BASE_LEN + DATE_LEN + CRLFCRLF_LEN + PLAINTEXT_BODY_LEN
-            } else {
-               core::ptr::copy_nonoverlapping(HTTP_404_NOTFOUND.as_ptr(), response_buffer, HTTP_404_NOTFOUND_LEN);
-               HTTP_404_NOTFOUND_LEN
-            }
-         } else {
-            core::ptr::copy_nonoverlapping(HTTP_405_NOTALLOWED.as_ptr(), response_buffer, HTTP_405_NOTALLOWED_LEN);
-            HTTP_405_NOTALLOWED_LEN
-         }
-      } else {
-         0
-      }
+fn port() -> u16 {
+   match std::env::var("PORT") {
+      Ok(port) => port.parse().expect("PORT must be a valid port number"),
+      Err(_) => DEFAULT_PORT,
    }
 }
 
 pub fn main() {
-   faf::epoll::go(8089, cb);
+   faf::epoll::go(port(), cb);
 }
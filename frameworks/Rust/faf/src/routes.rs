@@ -0,0 +1,105 @@
+//! A const-evaluated route table for `cb`.
+//!
+//! Routes are matched in order by method and path prefix, so the hottest route should come
+//! first. Responses are written with `JsonWriter`, which keeps track of the buffer offset.
+
+use core::intrinsics::likely;
+use faf::const_http::*;
+use faf::util::memcmp;
+
+use crate::json::JsonWriter;
+
+pub const CONTENT_LENGTH: &[u8] = b"Content-Length: ";
+
+/// Writes a full response, headers included, and returns its length
+pub type Handler = unsafe fn(response_buffer: *mut u8, date_buff: *const u8) -> usize;
+
+pub enum Response {
+   /// A fixed body. `head` runs from the status line up to and including `Content-Length: `
+   /// (see `static_head!`); the length, date and body are appended per request.
+   Static {
+      head: &'static [u8],
+      body: &'static [u8],
+   },
+   Handler(Handler),
+}
+
+pub struct Route {
+   pub method: &'static [u8],
+   pub path: &'static [u8],
+   pub response: Response,
+}
+
+impl Route {
+   pub const fn new(method: &'static [u8], path: &'static [u8], response: Response) -> Self {
+      Self { method, path, response }
+   }
+}
+
+/// Builds the `head` of a `Response::Static` from a `Content-Type: ...` header
+#[macro_export]
+macro_rules! static_head {
+   ($content_type:expr) => {
+      faf::const_concat_bytes!(
+         faf::const_http::HTTP_200_OK,
+         faf::const_http::CRLF,
+         faf::const_http::SERVER,
+         faf::const_http::CRLF,
+         $content_type,
+         faf::const_http::CRLF,
+         $crate::routes::CONTENT_LENGTH
+      )
+   };
+}
+
+#[inline]
+unsafe fn respond(response: &Response, response_buffer: *mut u8, date_buff: *const u8) -> usize {
+   match *response {
+      Response::Static { head, body } => {
+         let mut w = JsonWriter::new(response_buffer);
+         w.raw(head);
+         w.usize(body.len());
+         w.raw(CRLF);
+         w.raw(core::slice::from_raw_parts(date_buff, DATE_LEN));
+         w.raw(CRLFCRLF);
+         w.raw(body);
+         w.written()
+      }
+      Response::Handler(handler) => handler(response_buffer, date_buff),
+   }
+}
+
+/// Walks `routes` and writes the first match into `response_buffer`. A path that matches
+/// with the wrong method gets a 405, anything else a 404.
+#[inline(always)]
+pub unsafe fn dispatch(
+   routes: &[Route],
+   method: *const u8,
+   method_len: usize,
+   path: *const u8,
+   path_len: usize,
+   response_buffer: *mut u8,
+   date_buff: *const u8,
+) -> usize {
+   if method_len == 0 || path_len == 0 {
+      return 0;
+   }
+
+   let mut path_matched = false;
+   for route in routes {
+      if likely(path_len >= route.path.len() && memcmp(route.path.as_ptr(), path, route.path.len()) == 0) {
+         if likely(method_len >= route.method.len() && memcmp(route.method.as_ptr(), method, route.method.len()) == 0) {
+            return respond(&route.response, response_buffer, date_buff);
+         }
+         path_matched = true;
+      }
+   }
+
+   if path_matched {
+      core::ptr::copy_nonoverlapping(HTTP_405_NOTALLOWED.as_ptr(), response_buffer, HTTP_405_NOTALLOWED_LEN);
+      HTTP_405_NOTALLOWED_LEN
+   } else {
+      core::ptr::copy_nonoverlapping(HTTP_404_NOTFOUND.as_ptr(), response_buffer, HTTP_404_NOTFOUND_LEN);
+      HTTP_404_NOTFOUND_LEN
+   }
+}
@@ -1,6 +1,11 @@
+use std::cell::{Cell, RefCell};
+use std::io;
 use std::net::SocketAddr;
+use std::rc::Rc;
 
-use futures::{Future, Stream};
+use futures::{future, Future, Stream};
+use rand::rngs::SmallRng;
+use rand::{FromEntropy, Rng};
 use tokio_core::net::TcpStream;
 use tokio_core::reactor::Handle;
 use tokio_postgres::{Client, Config, NoTls, Statement};
@@ -8,6 +13,9 @@ use tokio_postgres::{Client, Config, NoTls, Statement};
 pub struct Db {
     client: Client,
     fortune: Statement,
+    world: Statement,
+    update: Statement,
+    rng: SmallRng,
 }
 
 pub struct Fortune {
@@ -15,6 +23,12 @@ pub struct Fortune {
     pub message: String,
 }
 
+#[derive(Serialize)]
+pub struct World {
+    pub id: i32,
+    pub randomnumber: i32,
+}
+
 pub fn connect(
     addr: SocketAddr,
     config: Config,
@@ -30,17 +44,141 @@ pub fn connect(
         .and_then(move |(mut client, conn)| {
             handle.spawn(conn.map_err(|e| panic!("postgres connection error: {}", e)));
 
-            client
-                .prepare("SELECT id, message FROM fortune")
+            let fortune = client.prepare("SELECT id, message FROM fortune");
+            let world = client.prepare("SELECT id, randomnumber FROM world WHERE id = $1");
+            // A single statement for every batch size: ids and new values are
+            // sent as two arrays and joined back together with UNNEST.
+            let update = client.prepare(
+                "UPDATE world SET randomnumber = v.randomnumber \
+                 FROM UNNEST($1::int[], $2::int[]) AS v (id, randomnumber) \
+                 WHERE world.id = v.id",
+            );
+
+            fortune
+                .join3(world, update)
                 .map_err(|_| ())
-                .map(move |fortune| Db { client, fortune })
+                .map(move |(fortune, world, update)| Db {
+                    client,
+                    fortune,
+                    world,
+                    update,
+                    rng: SmallRng::from_entropy(),
+                })
         })
 }
 
+/// The connections owned by one reactor thread, handed out round-robin to
+/// the HTTP connections accepted on it.
+pub struct Pool {
+    conns: Vec<Rc<RefCell<Db>>>,
+    next: Cell<usize>,
+}
+
+pub fn connect_pool(
+    size: usize,
+    addr: SocketAddr,
+    config: Config,
+    handle: Handle,
+) -> impl Future<Item = Pool, Error = ()> {
+    let conns: Vec<_> = (0..size)
+        .map(|_| connect(addr, config.clone(), handle.clone()))
+        .collect();
+
+    future::join_all(conns).map(|conns| Pool {
+        conns: conns
+            .into_iter()
+            .map(|db| Rc::new(RefCell::new(db)))
+            .collect(),
+        next: Cell::new(0),
+    })
+}
+
+impl Pool {
+    pub fn get(&self) -> Rc<RefCell<Db>> {
+        let idx = self.next.get();
+        self.next.set((idx + 1) % self.conns.len());
+        self.conns[idx].clone()
+    }
+}
+
+fn db_error(e: tokio_postgres::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
 impl Db {
-    pub fn tell_fortune(
-        &mut self,
-    ) -> impl Future<Item = Vec<Fortune>, Error = ::tokio_postgres::Error> {
+    fn random_id(&mut self) -> i32 {
+        self.rng.gen_range(1, 10_001)
+    }
+
+    fn query_world(&mut self, id: i32) -> impl Future<Item = World, Error = io::Error> {
+        self.client
+            .query(&self.world, &[&id])
+            .into_future()
+            .map_err(|(e, _)| db_error(e))
+            .and_then(move |(row, _)| match row {
+                Some(row) => Ok(World {
+                    id: row.get(0),
+                    randomnumber: row.get(1),
+                }),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("world {} not found", id),
+                )),
+            })
+    }
+
+    pub fn get_world(&mut self) -> impl Future<Item = World, Error = io::Error> {
+        let id = self.random_id();
+        self.query_world(id)
+    }
+
+    pub fn get_worlds(&mut self, num: usize) -> impl Future<Item = Vec<World>, Error = io::Error> {
+        let worlds: Vec<_> = (0..num)
+            .map(|_| {
+                let id = self.random_id();
+                self.query_world(id)
+            })
+            .collect();
+
+        future::join_all(worlds)
+    }
+
+    pub fn update(&mut self, num: usize) -> impl Future<Item = Vec<World>, Error = io::Error> {
+        let mut changes: Vec<(i32, i32)> = (0..num)
+            .map(|_| (self.random_id(), self.random_id()))
+            .collect();
+
+        let worlds: Vec<_> = changes
+            .iter()
+            .map(|&(id, randomnumber)| {
+                self.query_world(id).map(move |mut world| {
+                    world.randomnumber = randomnumber;
+                    world
+                })
+            })
+            .collect();
+
+        // The new values don't depend on what the selects return, so the
+        // update is pipelined right behind them on the same connection.
+        // Postgres runs them in order, so every row is read before it is
+        // written. Ids are sorted to keep concurrent batches from deadlocking,
+        // and a repeated id is written once, as Postgres would otherwise apply
+        // an arbitrary one of its values.
+        changes.sort_by_key(|&(id, _)| id);
+        changes.dedup_by_key(|&mut (id, _)| id);
+        let ids: Vec<i32> = changes.iter().map(|&(id, _)| id).collect();
+        let numbers: Vec<i32> = changes.iter().map(|&(_, n)| n).collect();
+        let update = self
+            .client
+            .execute(&self.update, &[&ids, &numbers])
+            .map_err(db_error);
+
+        future::join_all(worlds)
+            .join(update)
+            .map(|(worlds, _)| worlds)
+    }
+
+    pub fn tell_fortune(&mut self) -> impl Future<Item = Vec<Fortune>, Error = io::Error> {
         let items = vec![Fortune {
             id: 0,
             message: "Additional fortune added at request time.".to_string(),
@@ -53,11 +191,12 @@ impl Db {
                     id: row.get(0),
                     message: row.get(1),
                 });
-                Ok(items)
+                Ok::<_, tokio_postgres::Error>(items)
             })
             .map(|mut items| {
                 items.sort_by(|it, next| it.message.cmp(&next.message));
                 items
             })
+            .map_err(db_error)
     }
 }
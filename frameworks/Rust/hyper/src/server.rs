@@ -1,30 +1,55 @@
+use std::fmt::Debug;
 use std::io;
 use std::net::SocketAddr;
 use std::thread;
 
-use futures::{Future, Stream};
+use futures::{future, Future, Stream};
 use hyper::server::conn::Http;
 use tokio_core::net::{TcpListener, TcpStream};
 use tokio_core::reactor::{Core, Handle};
 
+// Only the plain binary calls this; `main_db` shares the module.
+#[allow(dead_code)]
 pub(crate) fn run<F>(per_connection: F)
 where
     F: Fn(TcpStream, &mut Http, &Handle) + Clone + Send + 'static,
+{
+    run_with_state(
+        |_| future::ok::<(), ()>(()),
+        move |socket, http, handle, _: &()| per_connection(socket, http, handle),
+    )
+}
+
+/// Like `run`, but first resolves `per_thread` on every reactor, and hands
+/// the result to each connection accepted by that reactor. This is how
+/// expensive per-thread resources, such as database connections, are
+/// shared by all the connections of a `Core` instead of being set up per
+/// socket.
+pub(crate) fn run_with_state<I, S, F>(per_thread: I, per_connection: F)
+where
+    I: Fn(&Handle) -> S + Clone + Send + 'static,
+    S: Future,
+    S::Error: Debug,
+    F: Fn(TcpStream, &mut Http, &Handle, &S::Item) + Clone + Send + 'static,
 {
     // Spawn a thread for each available core, minus one, since we'll
     // reuse the main thread as a server thread as well.
     for _ in 1..num_cpus::get() {
+        let per_thread = per_thread.clone();
         let per_connection = per_connection.clone();
         thread::spawn(move || {
-            server_thread(per_connection);
+            server_thread(per_thread, per_connection);
         });
     }
-    server_thread(per_connection);
+    server_thread(per_thread, per_connection);
 }
 
-fn server_thread<F>(per_connection: F)
+fn server_thread<I, S, F>(per_thread: I, per_connection: F)
 where
-    F: Fn(TcpStream, &mut Http, &Handle) + Send + 'static,
+    I: Fn(&Handle) -> S,
+    S: Future,
+    S::Error: Debug,
+    F: Fn(TcpStream, &mut Http, &Handle, &S::Item),
 {
     let mut http = Http::new();
     http.http1_only(true);
@@ -33,6 +58,9 @@ where
     let mut core = Core::new().expect("core");
     let handle = core.handle();
 
+    // Set up the thread's shared state before accepting anything.
+    let state = core.run(per_thread(&handle)).expect("per-thread state");
+
     // Bind to 0.0.0.0:8080
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
     let tcp = reuse_listener(&addr, &handle).expect("couldn't bind to addr");
@@ -42,7 +70,7 @@ where
         .incoming()
         .for_each(move |(sock, _addr)| {
             let _ = sock.set_nodelay(true);
-            per_connection(sock, &mut http, &handle);
+            per_connection(sock, &mut http, &handle, &state);
             Ok(())
         })
         .map_err(|e| eprintln!("accept error: {}", e));
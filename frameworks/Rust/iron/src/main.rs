@@ -13,6 +13,7 @@ extern crate r2d2;
 extern crate r2d2_postgres;
 extern crate rand;
 extern crate rustc_serialize;
+extern crate url;
 
 use hyper::header::{ContentType, Server};
 use iron::modifiers::Header;
@@ -23,6 +24,9 @@ use persistent::Read;
 use r2d2::Pool;
 use r2d2_postgres::{PostgresConnectionManager, TlsMode};
 use rand::distributions::{IndependentSample, Range};
+use std::cmp;
+use std::sync::Arc;
+use url::form_urlencoded;
 
 #[derive(Serialize, Deserialize)]
 struct Message {
@@ -38,7 +42,7 @@ struct DatabaseRow {
 
 struct CachedRows;
 impl Key for CachedRows {
-    type Value = Vec<DatabaseRow>;
+    type Value = Arc<[DatabaseRow]>;
 }
 
 pub type PostgresPool = Pool<PostgresConnectionManager>;
@@ -78,19 +82,17 @@ fn main() {
     )
     .unwrap();
 
-    let mut cached_rows: Vec<DatabaseRow> = Vec::with_capacity(10000);
     let conn = pool.get().unwrap();
-
-    for num in 1..10000 {
-        let rows = &conn
-            .query("SELECT id, randomnumber FROM World WHERE id = $1", &[&num])
-            .unwrap();
-        let row = rows.get(0);
-        cached_rows.push(DatabaseRow {
+    let cached_rows: Arc<[DatabaseRow]> = conn
+        .query("SELECT id, randomnumber FROM World ORDER BY id", &[])
+        .unwrap()
+        .iter()
+        .map(|row| DatabaseRow {
             id: row.get(0),
             randomNumber: row.get(1),
-        });
-    }
+        })
+        .collect::<Vec<_>>()
+        .into();
 
     let app = router!(
         json: get "/json" => json_handler,
@@ -147,18 +149,7 @@ fn queries_handler(req: &mut Request) -> IronResult<Response> {
     let content_type = Header(ContentType::json());
     let server = Header(Server("Iron".to_owned()));
     let pool = req.get::<Read<DbPool>>().unwrap();
-    let query = req.url.query().unwrap();
-    let param = match get_param(query, "queries") {
-        Some(n) => match n.parse::<usize>() {
-            Ok(m) => match m {
-                e @ 1...500 => e,
-                e if e > 500 => 500,
-                _ => 1,
-            },
-            _ => 1,
-        },
-        _ => 1,
-    };
+    let param = queries_param(req);
     let mut res: Vec<DatabaseRow> = Vec::with_capacity(param);
     for _ in 0..param {
         let conn = pool.get().unwrap();
@@ -175,26 +166,14 @@ fn queries_handler(req: &mut Request) -> IronResult<Response> {
 fn cached_queries_handler(req: &mut Request) -> IronResult<Response> {
     let content_type = Header(ContentType::json());
     let server = Header(Server("Iron".to_owned()));
-    let cached_rows = req.get::<Read<CachedRows>>().unwrap().to_owned();
-    let query = req.url.query().unwrap();
-    let param = match get_param(query, "queries") {
-        Some(n) => match n.parse::<usize>() {
-            Ok(m) => match m {
-                e @ 1...500 => e,
-                e if e > 500 => 500,
-                _ => 1,
-            },
-            _ => 1,
-        },
-        _ => 1,
-    };
+    let cached_rows = req.get::<Read<CachedRows>>().unwrap();
+    let param = queries_param(req);
 
+    let mut rng = rand::thread_rng();
+    let between = Range::new(0, cached_rows.len());
     let mut res: Vec<DatabaseRow> = Vec::with_capacity(param);
     for _ in 0..param {
-        let mut rng = rand::thread_rng();
-        let between = Range::new(1, 10000);
-        let num = between.ind_sample(&mut rng);
-        res.push(cached_rows[num].to_owned())
+        res.push(cached_rows[between.ind_sample(&mut rng)].clone())
     }
     Ok(Response::with((
         status::Ok,
@@ -229,23 +208,10 @@ fn fortune_handler(req: &mut Request) -> IronResult<Response> {
 }
 
 fn updates_handler(req: &mut Request) -> IronResult<Response> {
-    let mut rng = rand::thread_rng();
-    let between = Range::new(1, 10000);
     let content_type = Header(ContentType::json());
     let server = Header(Server("Iron".to_owned()));
     let pool = req.get::<Read<DbPool>>().unwrap();
-    let query = req.url.query().unwrap();
-    let param = match get_param(query, "queries") {
-        Some(n) => match n.parse::<usize>() {
-            Ok(m) => match m {
-                e @ 1...500 => e,
-                e if e > 500 => 500,
-                _ => 1,
-            },
-            _ => 1,
-        },
-        _ => 1,
-    };
+    let param = queries_param(req);
     let mut dbres: Vec<DatabaseRow> = Vec::with_capacity(param);
     for _ in 0..param {
         let conn = pool.get().unwrap();
@@ -257,7 +223,7 @@ fn updates_handler(req: &mut Request) -> IronResult<Response> {
     dbres.sort_by_key(|it| it.id);
     let mut res: Vec<DatabaseRow> = Vec::with_capacity(param);
     for row in dbres {
-        let num = between.ind_sample(&mut rng);
+        let num = random_id();
         trans
             .execute(
                 "UPDATE World SET randomnumber = $1 WHERE id = $2",
@@ -279,9 +245,7 @@ fn updates_handler(req: &mut Request) -> IronResult<Response> {
 }
 
 fn random_row(conn: r2d2::PooledConnection<PostgresConnectionManager>) -> DatabaseRow {
-    let mut rng = rand::thread_rng();
-    let between = Range::new(1, 10000);
-    let num = between.ind_sample(&mut rng);
+    let num = random_id();
     let rows = &conn
         .query("SELECT id, randomnumber FROM World WHERE id = $1", &[&num])
         .unwrap();
@@ -292,12 +256,23 @@ fn random_row(conn: r2d2::PooledConnection<PostgresConnectionManager>) -> Databa
     }
 }
 
-fn get_param<'a>(querystring: &'a str, param: &'a str) -> Option<&'a str> {
-    let n = querystring
-        .split("&")
-        .find(|&it| !(it.find(param).is_none()));
-    match n {
-        Some(n) => n.split("=").nth(1),
-        _ => n,
-    }
+/// A world id or random number, in `1..=10000`.
+fn random_id() -> i32 {
+    let mut rng = rand::thread_rng();
+    Range::new(1, 10001).ind_sample(&mut rng)
+}
+
+/// The `queries` query string parameter, clamped to `1..=500`. A missing or
+/// malformed value counts as 1.
+fn queries_param(req: &Request) -> usize {
+    let queries = req
+        .url
+        .query()
+        .and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|&(ref key, _)| key == "queries")
+                .and_then(|(_, value)| value.parse().ok())
+        })
+        .unwrap_or(1);
+    cmp::max(1, cmp::min(500, queries))
 }
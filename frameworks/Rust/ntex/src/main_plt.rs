@@ -3,7 +3,8 @@ static GLOBAL: snmalloc_rs::SnMalloc = snmalloc_rs::SnMalloc;
 use std::{future::Future, io, pin::Pin, task::Context, task::Poll};
 
 use ntex::{
-    fn_service, http::h1, io::Io, io::RecvError, util::ready, util::BufMut, util::PoolId,
+    fn_service, http::h1, http::Method, http::Version, io::Io, io::RecvError, util::ready,
+    util::BufMut, util::PoolId,
 };
 mod utils;
 
@@ -14,9 +15,14 @@ const JSON: &[u8] =
     b"HTTP/1.1 200 OK\r\nServer: N\r\nContent-Type: application/json\r\nContent-Length: 27\r\n";
 const PLAIN: &[u8] =
     b"HTTP/1.1 200 OK\r\nServer: N\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n";
-const HTTPNFOUND: &[u8] = b"HTTP/1.1 400 OK\r\n";
-const HDR_SERVER: &[u8] = b"Server: N\r\n";
+const HTTPNFOUND: &[u8] =
+    b"HTTP/1.1 404 Not Found\r\nServer: N\r\nContent-Type: text/plain\r\nContent-Length: 9\r\n";
+const HTTPNALLOWED: &[u8] = b"HTTP/1.1 405 Method Not Allowed\r\nServer: N\r\nAllow: GET, HEAD\r\nContent-Type: text/plain\r\nContent-Length: 18\r\n";
+const HDR_CLOSE: &[u8] = b"Connection: close\r\n";
+const HDR_KEEPALIVE: &[u8] = b"Connection: keep-alive\r\n";
 const BODY: &[u8] = b"Hello, World!";
+const BODY_NFOUND: &[u8] = b"Not Found";
+const BODY_NALLOWED: &[u8] = b"Method Not Allowed";
 
 #[derive(serde::Serialize)]
 pub struct Message {
@@ -26,6 +32,7 @@ pub struct Message {
 struct App {
     io: Io,
     codec: h1::Codec,
+    closing: bool,
 }
 
 impl Future for App {
@@ -34,8 +41,31 @@ impl Future for App {
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.as_mut().get_mut();
         loop {
+            // the last response is buffered, flush it and close the connection
+            if this.closing {
+                let _ = ready!(this.io.poll_shutdown(cx));
+                return Poll::Ready(Ok(()));
+            }
+
             match ready!(this.io.poll_recv(&this.codec, cx)) {
-                Ok((req, _)) => {
+                Ok((req, payload)) => {
+                    let head = req.head();
+                    let head_only = head.method == Method::HEAD;
+                    let allowed = head_only || head.method == Method::GET;
+
+                    // the codec already applied the HTTP/1.0 and `Connection` header
+                    // rules. request bodies are never read, so a request carrying one
+                    // has to be the last on the connection.
+                    let hdr_conn =
+                        if !this.codec.keepalive() || !matches!(payload, h1::PayloadType::None) {
+                            this.closing = true;
+                            Some(HDR_CLOSE)
+                        } else if head.version == Version::HTTP_10 {
+                            Some(HDR_KEEPALIVE)
+                        } else {
+                            None
+                        };
+
                     let _ = this.io.with_write_buf(|buf| {
                         // make sure we've got room
                         let remaining = buf.remaining_mut();
@@ -43,25 +73,30 @@ impl Future for App {
                             buf.reserve(65535 - remaining);
                         }
 
-                        match req.path() {
-                            "/json" => {
-                                buf.extend_from_slice(JSON);
-                                this.codec.set_date_header(buf);
-                                let _ = simd_json::to_writer(
-                                    crate::utils::Writer(buf),
-                                    &Message {
-                                        message: "Hello, World!",
-                                    },
-                                );
-                            }
-                            "/plaintext" => {
-                                buf.extend_from_slice(PLAIN);
-                                this.codec.set_date_header(buf);
-                                buf.extend_from_slice(BODY);
-                            }
-                            _ => {
-                                buf.extend_from_slice(HTTPNFOUND);
-                                buf.extend_from_slice(HDR_SERVER);
+                        let (hdrs, body) = match req.path() {
+                            "/json" if allowed => (JSON, None),
+                            "/plaintext" if allowed => (PLAIN, Some(BODY)),
+                            "/json" | "/plaintext" => (HTTPNALLOWED, Some(BODY_NALLOWED)),
+                            _ => (HTTPNFOUND, Some(BODY_NFOUND)),
+                        };
+
+                        buf.extend_from_slice(hdrs);
+                        if let Some(hdr_conn) = hdr_conn {
+                            buf.extend_from_slice(hdr_conn);
+                        }
+                        this.codec.set_date_header(buf);
+
+                        if !head_only {
+                            match body {
+                                Some(body) => buf.extend_from_slice(body),
+                                None => {
+                                    let _ = simd_json::to_writer(
+                                        crate::utils::Writer(buf),
+                                        &Message {
+                                            message: "Hello, World!",
+                                        },
+                                    );
+                                }
                             }
                         }
                     });
@@ -92,6 +127,7 @@ async fn main() -> io::Result<()> {
             fn_service(|io| App {
                 io,
                 codec: h1::Codec::default(),
+                closing: false,
             })
         })?
         .run()
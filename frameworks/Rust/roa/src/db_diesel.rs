@@ -1,64 +1,49 @@
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::r2d2::ConnectionManager;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{Array, Integer};
 use roa::http::StatusCode;
-use roa_diesel::preload::*;
-use roa_diesel::Pool;
-
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
+use roa::Executor;
 
 use crate::models::Fortune;
-use crate::{async_trait, throw, Context, Result, Service, StdResult, World};
-use futures::stream::{FuturesUnordered, TryStreamExt};
-
-#[derive(Clone)]
-pub struct State {
-    pool: Pool<PgConnection>,
-    rng: SmallRng,
-}
+use crate::{async_trait, throw, Backend, Result, StdResult, World};
 
-impl AsRef<Pool<PgConnection>> for State {
-    #[inline]
-    fn as_ref(&self) -> &Pool<PgConnection> {
-        &self.pool
-    }
+pub struct Db {
+    pool: Pool<ConnectionManager<PgConnection>>,
 }
 
-impl State {
-    pub async fn bind(pg_url: &str) -> StdResult<Self> {
+impl Db {
+    pub async fn bind(pg_url: &str, pool_size: u32) -> StdResult<Self> {
         let pool = Pool::builder()
-            .max_size(50)
+            .max_size(pool_size)
             .build(ConnectionManager::<PgConnection>::new(pg_url))?;
-        Ok(Self {
-            pool,
-            rng: SmallRng::from_entropy(),
-        })
-    }
-}
-
-#[async_trait(?Send)]
-impl Service for Context<State> {
-    #[inline]
-    fn random_id(&mut self) -> i32 {
-        self.rng.gen_range(0, 10_001)
+        Ok(Self { pool })
     }
 
-    #[inline]
-    fn get_queries(&self) -> usize {
-        use std::cmp::{max, min};
-        let query = self.uri().query();
-        let nums = query
-            .and_then(|query| Some((query, query.find("q")?)))
-            .and_then(|(query, pos)| query.split_at(pos + 2).1.parse().ok())
-            .unwrap_or(1);
-        min(500, max(1, nums))
+    /// Runs `f` with a pooled connection on the app's blocking threads.
+    async fn run<F, T>(&self, exec: &Executor, f: F) -> Result<T>
+    where
+        F: 'static + Send + FnOnce(&PgConnection) -> QueryResult<T>,
+        T: 'static + Send,
+    {
+        let pool = self.pool.clone();
+        let data = exec
+            .spawn_blocking(move || pool.get().map(|conn| f(&conn)))
+            .await;
+        Ok(data??)
     }
+}
 
+#[async_trait(?Send)]
+impl Backend for Db {
     #[inline]
-    async fn query_world(&self, wid: i32) -> Result<World> {
-        use crate::schema::world::dsl::*;
-        let data = self.first(world.filter(id.eq(wid))).await?;
+    async fn query_world(&self, exec: &Executor, wid: i32) -> Result<World> {
+        let data = self
+            .run(exec, move |conn| {
+                use crate::schema::world::dsl::*;
+                world.filter(id.eq(wid)).first(conn).optional()
+            })
+            .await?;
         match data {
             None => throw!(StatusCode::NOT_FOUND),
             Some(item) => Ok(item),
@@ -66,32 +51,51 @@ impl Service for Context<State> {
     }
 
     #[inline]
-    async fn fortunes(&self) -> Result<Vec<Fortune>> {
-        use crate::schema::fortune::dsl::*;
-        Ok(self.load_data(fortune).await?)
+    async fn fortunes(&self, exec: &Executor) -> Result<Vec<Fortune>> {
+        self.run(exec, |conn| {
+            use crate::schema::fortune::dsl::*;
+            fortune.load(conn)
+        })
+        .await
     }
 
     #[inline]
-    async fn update_worlds(&mut self) -> Result<Vec<World>> {
-        let worlds = FuturesUnordered::new();
-        let random_ids: Vec<_> =
-            (0..self.get_queries()).map(|_| self.random_id()).collect();
-        for wid in random_ids {
-            worlds.push(update_world(self, wid));
+    async fn save_worlds(&self, exec: &Executor, worlds: &[World]) -> Result {
+        let mut changes: Vec<(i32, i32)> =
+            worlds.iter().map(|w| (w.id, w.randomnumber)).collect();
+        changes.dedup_by_key(|&mut (wid, _)| wid);
+        if cfg!(feature = "per-row-updates") {
+            self.run(exec, move |conn| update_per_row(conn, &changes))
+                .await
+        } else {
+            self.run(exec, move |conn| update_batch(conn, &changes))
+                .await
         }
-        worlds.try_collect().await
     }
 }
 
-async fn update_world(ctx: &Context<State>, wid: i32) -> Result<World> {
-    use crate::schema::world::dsl::*;
-    let mut data = ctx.query_world(wid).await?;
-    data.randomnumber = wid;
-    ctx.execute(
-        diesel::update(world)
-            .filter(id.eq(wid))
-            .set(randomnumber.eq(wid)),
+fn update_batch(conn: &PgConnection, changes: &[(i32, i32)]) -> QueryResult<()> {
+    let (ids, numbers): (Vec<i32>, Vec<i32>) = changes.iter().copied().unzip();
+    diesel::sql_query(
+        "UPDATE world SET randomnumber = v.randomnumber \
+         FROM UNNEST($1::int[], $2::int[]) AS v (id, randomnumber) \
+         WHERE world.id = v.id",
     )
-    .await?;
-    Ok(data)
+    .bind::<Array<Integer>, _>(ids)
+    .bind::<Array<Integer>, _>(numbers)
+    .execute(conn)?;
+    Ok(())
+}
+
+fn update_per_row(conn: &PgConnection, changes: &[(i32, i32)]) -> QueryResult<()> {
+    use crate::schema::world::dsl::*;
+    conn.transaction(|| {
+        for &(wid, number) in changes {
+            diesel::update(world)
+                .filter(id.eq(wid))
+                .set(randomnumber.eq(number))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
 }
@@ -1,19 +1,15 @@
 use crate::models::Fortune;
-use crate::{async_trait, throw, Context, Result, Service, StdResult, World};
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
+use crate::{async_trait, throw, Backend, Result, StdResult, World};
 use roa::http::StatusCode;
+use roa::Executor;
 use roa_pg::types::ToSql;
 use roa_pg::{connect, Client, Statement};
 use std::collections::HashMap;
 use std::fmt::Write;
-use std::sync::Arc;
 
-#[derive(Clone)]
-pub struct State {
-    client: Arc<Client>,
-    queries: Arc<Queries>,
-    rng: SmallRng,
+pub struct Db {
+    client: Client,
+    queries: Queries,
 }
 
 pub struct Queries {
@@ -22,10 +18,12 @@ pub struct Queries {
     updates: HashMap<usize, Statement>,
 }
 
-impl State {
+impl Db {
     pub async fn bind(url: &str) -> StdResult<Self> {
         let (client, conn) = connect(&url.parse()?).await?;
 
+        // roa-pg's connection runs on async-std's own reactor, so it is spawned
+        // there even when the app itself runs on tokio.
         async_std::task::spawn(conn);
 
         let fortune = client.prepare("SELECT * FROM fortune").await?;
@@ -48,38 +46,21 @@ impl State {
             q.push(')');
             updates.insert(num, client.prepare(&q).await?);
         }
-        Ok(State {
-            client: Arc::new(client),
-            queries: Arc::new(Queries {
+        Ok(Db {
+            client,
+            queries: Queries {
                 fortune,
                 world,
                 updates,
-            }),
-            rng: SmallRng::from_entropy(),
+            },
         })
     }
 }
 
 #[async_trait(?Send)]
-impl Service for Context<State> {
-    #[inline]
-    fn random_id(&mut self) -> i32 {
-        self.rng.gen_range(0, 10_001)
-    }
-
+impl Backend for Db {
     #[inline]
-    fn get_queries(&self) -> usize {
-        use std::cmp::{max, min};
-        let query = self.uri().query();
-        let nums = query
-            .and_then(|query| Some((query, query.find("q")?)))
-            .and_then(|(query, pos)| query.split_at(pos + 2).1.parse().ok())
-            .unwrap_or(1);
-        min(500, max(1, nums))
-    }
-
-    #[inline]
-    async fn query_world(&self, wid: i32) -> Result<World> {
+    async fn query_world(&self, _exec: &Executor, wid: i32) -> Result<World> {
         match self.client.query_opt(&self.queries.world, &[&wid]).await? {
             None => throw!(StatusCode::NOT_FOUND),
             Some(row) => Ok(World {
@@ -90,7 +71,7 @@ impl Service for Context<State> {
     }
 
     #[inline]
-    async fn fortunes(&self) -> Result<Vec<Fortune>> {
+    async fn fortunes(&self, _exec: &Executor) -> Result<Vec<Fortune>> {
         let fortunes = self
             .client
             .query(&self.queries.fortune, &[])
@@ -105,22 +86,17 @@ impl Service for Context<State> {
     }
 
     #[inline]
-    async fn update_worlds(&mut self) -> Result<Vec<World>> {
-        let mut worlds = self.query_worlds().await?;
-        let nums = worlds.len();
-        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(nums * 3);
-        for w in worlds.iter_mut() {
-            w.randomnumber = w.id;
-        }
-        for w in &worlds {
+    async fn save_worlds(&self, _exec: &Executor, worlds: &[World]) -> Result {
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(worlds.len() * 3);
+        for w in worlds {
             params.push(&w.id);
             params.push(&w.randomnumber);
         }
-        for w in &worlds {
+        for w in worlds {
             params.push(&w.id);
         }
-        let statement = &self.queries.updates[&nums];
+        let statement = &self.queries.updates[&worlds.len()];
         self.client.execute(statement, &params).await?;
-        Ok(worlds)
+        Ok(())
     }
 }
@@ -1,24 +1,34 @@
 use crate::models::Fortune;
-use crate::{async_trait, throw, Context, Result, Service, StdResult, World};
+use crate::{async_trait, throw, Backend, Result, StdResult, World};
 use futures::TryStreamExt;
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
 use roa::http::StatusCode;
+use roa::Executor;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{PgPool, Row};
 use std::collections::HashMap;
 use std::fmt::Write;
-use std::sync::Arc;
 
-#[derive(Clone)]
-pub struct State {
+/// The world and fortune selects, plus one update per batch size.
+const STATEMENTS: usize = 2 + 500;
+
+pub struct Db {
     client: PgPool,
-    updates: Arc<HashMap<usize, String>>,
-    rng: SmallRng,
+    updates: HashMap<usize, String>,
 }
 
-impl State {
-    pub async fn bind(url: &str) -> StdResult<Self> {
-        let client = PgPool::new(url).await?;
+impl Db {
+    /// Opens `pool_size` connections. sqlx prepares every query once per
+    /// connection and keeps it; the cache is sized so the 500 update
+    /// statements never push the selects out.
+    pub async fn bind(url: &str, pool_size: u32) -> StdResult<Self> {
+        let options = url
+            .parse::<PgConnectOptions>()?
+            .statement_cache_capacity(STATEMENTS);
+        let client = PgPoolOptions::new()
+            .max_connections(pool_size)
+            .min_connections(pool_size)
+            .connect_with(options)
+            .await?;
         let mut updates = HashMap::new();
         for num in 1..=500 {
             let mut pl = 1;
@@ -37,37 +47,17 @@ impl State {
             q.push(')');
             updates.insert(num, q);
         }
-        Ok(State {
-            client,
-            updates: Arc::new(updates),
-            rng: SmallRng::from_entropy(),
-        })
+        Ok(Db { client, updates })
     }
 }
 
 #[async_trait(?Send)]
-impl Service for Context<State> {
-    #[inline]
-    fn random_id(&mut self) -> i32 {
-        self.rng.gen_range(0, 10_001)
-    }
-
-    #[inline]
-    fn get_queries(&self) -> usize {
-        use std::cmp::{max, min};
-        let query = self.uri().query();
-        let nums = query
-            .and_then(|query| Some((query, query.find("q")?)))
-            .and_then(|(query, pos)| query.split_at(pos + 2).1.parse().ok())
-            .unwrap_or(1);
-        min(500, max(1, nums))
-    }
-
+impl Backend for Db {
     #[inline]
-    async fn query_world(&self, wid: i32) -> Result<World> {
+    async fn query_world(&self, _exec: &Executor, wid: i32) -> Result<World> {
         match sqlx::query("SELECT * FROM world WHERE id=$1")
             .bind(wid)
-            .fetch_optional(&mut &self.client)
+            .fetch_optional(&self.client)
             .await?
         {
             None => throw!(StatusCode::NOT_FOUND),
@@ -79,9 +69,9 @@ impl Service for Context<State> {
     }
 
     #[inline]
-    async fn fortunes(&self) -> Result<Vec<Fortune>> {
+    async fn fortunes(&self, _exec: &Executor) -> Result<Vec<Fortune>> {
         let fortunes: Vec<_> = sqlx::query("SELECT * FROM fortune")
-            .fetch(&mut &self.client)
+            .fetch(&self.client)
             .map_ok(|row| Fortune {
                 id: row.get(0),
                 message: row.get(1),
@@ -92,17 +82,17 @@ impl Service for Context<State> {
     }
 
     #[inline]
-    async fn update_worlds(&mut self) -> Result<Vec<World>> {
-        let mut worlds = self.query_worlds().await?;
+    async fn save_worlds(&self, _exec: &Executor, worlds: &[World]) -> Result {
         let mut query = sqlx::query(&self.updates[&worlds.len()]);
-        for w in worlds.iter_mut() {
-            w.randomnumber = w.id;
+        for w in worlds {
             query = query.bind(w.id).bind(w.randomnumber);
         }
-        for w in &worlds {
+        for w in worlds {
             query = query.bind(w.id);
         }
-        query.execute(&mut &self.client).await?;
-        Ok(worlds)
+        let mut tx = self.client.begin().await?;
+        query.execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
     }
 }
@@ -8,26 +8,24 @@ mod db_diesel;
 #[cfg(feature = "orm")]
 mod schema;
 
-#[cfg(feature = "orm")]
-use db_diesel::State;
-
 #[cfg(feature = "pg")]
 mod db_pg;
 
-#[cfg(feature = "pg")]
-use db_pg::State;
-
 #[cfg(feature = "sqlx-pg")]
 mod db_sqlx;
 
-#[cfg(feature = "sqlx-pg")]
-use db_sqlx::State;
-
 use futures::stream::{FuturesUnordered, TryStreamExt};
+use rand::distributions::Uniform;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 use roa::http::header::SERVER;
 use roa::preload::*;
 use roa::router::{get, RouteTable, Router};
-use roa::{async_trait, throw, App, Context, Next, Result};
+use roa::{async_trait, throw, App, Context, Executor, Next, Result};
+use std::cell::RefCell;
+use std::env;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
 mod models;
 pub mod utils;
 use dotenv_codegen::dotenv;
@@ -36,22 +34,128 @@ use utils::SERVER_HEADER;
 
 type StdResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// A database backend. Backends only run queries; the benchmark rules live in
+/// `Service`, so they can't drift between backends.
+#[async_trait(?Send)]
+trait Backend: Send + Sync {
+    async fn query_world(&self, exec: &Executor, wid: i32) -> Result<World>;
+    async fn fortunes(&self, exec: &Executor) -> Result<Vec<Fortune>>;
+    /// Writes back the `randomnumber` of every world; ids are already sorted.
+    async fn save_worlds(&self, exec: &Executor, worlds: &[World]) -> Result;
+}
+
+/// Backends compiled into this binary, in order of preference.
+const BACKENDS: &[&str] = &[
+    #[cfg(feature = "orm")]
+    "diesel",
+    #[cfg(feature = "pg")]
+    "pg",
+    #[cfg(feature = "sqlx-pg")]
+    "sqlx",
+];
+
+/// Connections per pooled backend, unless `ROA_POOL_SIZE` says otherwise.
+const DEFAULT_POOL_SIZE: u32 = 50;
+
+/// Connects the backend named by the first argument or `ROA_BACKEND`, falling
+/// back to the first one compiled in.
+async fn connect(url: &str) -> StdResult<Arc<dyn Backend>> {
+    let pool_size = match env::var("ROA_POOL_SIZE") {
+        Ok(size) => size.parse()?,
+        Err(_) => DEFAULT_POOL_SIZE,
+    };
+    let name = env::args()
+        .nth(1)
+        .or_else(|| env::var("ROA_BACKEND").ok())
+        .or_else(|| BACKENDS.first().map(|name| name.to_string()))
+        .ok_or("no database backend enabled, build with `orm`, `pg` or `sqlx-pg`")?;
+    match name.as_str() {
+        #[cfg(feature = "orm")]
+        "diesel" => Ok(Arc::new(db_diesel::Db::bind(url, pool_size).await?)),
+        #[cfg(feature = "pg")]
+        "pg" => Ok(Arc::new(db_pg::Db::bind(url).await?)),
+        #[cfg(feature = "sqlx-pg")]
+        "sqlx" => Ok(Arc::new(db_sqlx::Db::bind(url, pool_size).await?)),
+        _ => Err(
+            format!("unknown backend {:?}, expected one of {:?}", name, BACKENDS).into(),
+        ),
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    db: Arc<dyn Backend>,
+}
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// TFB ids and random numbers both fall in this range.
+const WORLD_RANGE: RangeInclusive<i32> = 1..=10_000;
+
 #[async_trait(?Send)]
 trait Service {
-    fn random_id(&mut self) -> i32;
     fn get_queries(&self) -> usize;
     async fn query_world(&self, wid: i32) -> Result<World>;
     async fn fortunes(&self) -> Result<Vec<Fortune>>;
-    async fn update_worlds(&mut self) -> Result<Vec<World>>;
-    async fn query_worlds(&mut self) -> Result<Vec<World>> {
+    /// Writes back the `randomnumber` of every world; ids are already sorted.
+    async fn save_worlds(&self, worlds: &[World]) -> Result;
+
+    // The defaults below carry the benchmark rules, so implementors only have
+    // to hand the queries to a backend.
+
+    fn random_id(&self) -> i32 {
+        RNG.with(|rng| rng.borrow_mut().sample(Uniform::from(WORLD_RANGE)))
+    }
+
+    async fn query_worlds(&self) -> Result<Vec<World>> {
         let worlds = FuturesUnordered::new();
-        let random_ids: Vec<_> =
-            (0..self.get_queries()).map(|_| self.random_id()).collect();
-        for id in random_ids {
-            worlds.push(self.query_world(id));
+        for _ in 0..self.get_queries() {
+            worlds.push(self.query_world(self.random_id()));
         }
         worlds.try_collect().await
     }
+
+    async fn update_worlds(&self) -> Result<Vec<World>> {
+        let mut worlds = self.query_worlds().await?;
+        for world in worlds.iter_mut() {
+            world.randomnumber = self.random_id();
+        }
+        // lock rows in the same order across concurrent batches
+        worlds.sort_by_key(|world| world.id);
+        self.save_worlds(&worlds).await?;
+        Ok(worlds)
+    }
+}
+
+#[async_trait(?Send)]
+impl Service for Context<State> {
+    #[inline]
+    fn get_queries(&self) -> usize {
+        use std::cmp::{max, min};
+        let query = self.uri().query();
+        let nums = query
+            .and_then(|query| Some((query, query.find("q")?)))
+            .and_then(|(query, pos)| query.split_at(pos + 2).1.parse().ok())
+            .unwrap_or(1);
+        min(500, max(1, nums))
+    }
+
+    #[inline]
+    async fn query_world(&self, wid: i32) -> Result<World> {
+        self.db.query_world(&self.exec, wid).await
+    }
+
+    #[inline]
+    async fn fortunes(&self) -> Result<Vec<Fortune>> {
+        self.db.fortunes(&self.exec).await
+    }
+
+    #[inline]
+    async fn save_worlds(&self, worlds: &[World]) -> Result {
+        self.db.save_worlds(&self.exec, worlds).await
+    }
 }
 
 #[inline]
@@ -107,12 +211,30 @@ fn routes(prefix: &'static str) -> StdResult<RouteTable<State>> {
         .map_err(Into::into)
 }
 
+async fn state() -> StdResult<State> {
+    Ok(State {
+        db: connect(dotenv!("DATABASE_URL")).await?,
+    })
+}
+
+#[cfg(not(feature = "tokio_rt"))]
 #[async_std::main]
 async fn main() -> StdResult<()> {
-    let app = App::state(State::bind(dotenv!("DATABASE_URL")).await?).end(routes("/")?);
+    let app = App::state(state().await?).end(routes("/")?);
     app.listen("0.0.0.0:8080", |addr| {
         println!("Server listen on {}...", addr);
     })?
     .await?;
     Ok(())
 }
+
+#[cfg(feature = "tokio_rt")]
+#[tokio::main]
+async fn main() -> StdResult<()> {
+    use roa_tokio::{Exec, TcpIncoming};
+    let app = App::with_exec(state().await?, Exec).end(routes("/")?);
+    let incoming = TcpIncoming::bind("0.0.0.0:8080")?;
+    println!("Server listen on {}...", incoming.local_addr());
+    app.accept(incoming).await?;
+    Ok(())
+}
@@ -0,0 +1,16 @@
+//! World ids and random numbers for `main-db` and `main-pg`.
+
+use std::cell::RefCell;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// A world id or random number, in `1..=10_000`.
+pub fn random_number() -> i32 {
+    RNG.with(|rng| Uniform::new_inclusive(1, 10_000).sample(&mut *rng.borrow_mut()))
+}
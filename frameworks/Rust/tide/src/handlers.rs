@@ -1,7 +1,8 @@
 //! Functions that handle each request, returning a valid response.
 
 use crate::{
-    db::Pool,
+    db::{update_worlds, State},
+    middleware::CountExt,
     models::{Fortune, Message, World},
     rand::random_10k,
 };
@@ -10,12 +11,12 @@ use diesel::prelude::*;
 use tide::{Body, Error, Request, Response, Result};
 
 /// Return a static plaintext body.
-pub async fn plaintext(_request: Request<Pool>) -> Result<Response> {
+pub async fn plaintext(_request: Request<State>) -> Result<Response> {
     Ok(Response::builder(200).body("Hello, World!").build())
 }
 
 /// Return a static JSON body.
-pub async fn json(_request: Request<Pool>) -> Result<Response> {
+pub async fn json(_request: Request<State>) -> Result<Response> {
     Ok(Response::builder(200)
         .body(Body::from_json(&Message {
             message: "Hello, World!",
@@ -36,44 +37,28 @@ fn load_world(connection: &crate::db::Connection) -> Result<World> {
 }
 
 /// Return a single random `World` as JSON.
-pub async fn db(request: Request<Pool>) -> Result<Response> {
-    let connection = request.state().get()?;
-    let loaded_world = load_world(&connection)?;
+pub async fn db(request: Request<State>) -> Result<Response> {
+    let loaded_world = request.state().run(load_world).await?;
 
     Ok(Response::builder(200)
         .body(Body::from_json(&loaded_world)?)
         .build())
 }
 
-/// Get the count parameter from the request route, normalizing invalid values.
-///
-/// Return an integer in the range 1 - 500.
-fn get_count_param<T>(request: &Request<T>) -> usize {
-    const _COUNT_MIN: usize = 1;
-    const _COUNT_MAX: usize = 500;
-
-    let mut count = request
-        .param("count")
-        .unwrap_or("1")
-        .parse()
-        .unwrap_or(_COUNT_MIN);
-    if count > _COUNT_MAX {
-        count = _COUNT_MAX;
-    } else if count < _COUNT_MIN {
-        count = _COUNT_MIN;
-    }
-    count
-}
-
 /// Return a variable number of random `World`s as a JSON list.
-pub async fn queries(request: Request<Pool>) -> Result<Response> {
-    let count = get_count_param(&request);
-
-    let mut loaded_worlds = Vec::with_capacity(count);
-    let connection = request.state().get()?;
-    for _index in 0..count {
-        loaded_worlds.push(load_world(&connection)?);
-    }
+pub async fn queries(request: Request<State>) -> Result<Response> {
+    let count = request.count();
+
+    let loaded_worlds = request
+        .state()
+        .run(move |connection| {
+            let mut loaded_worlds = Vec::with_capacity(count);
+            for _index in 0..count {
+                loaded_worlds.push(load_world(connection)?);
+            }
+            Ok(loaded_worlds)
+        })
+        .await?;
 
     Ok(Response::builder(200)
         .body(Body::from_json(&loaded_worlds)?)
@@ -88,13 +73,17 @@ struct FortuneTemplate<'a> {
 }
 
 /// Return all `Fortune`s rendered as HTML.
-pub async fn fortunes(request: Request<Pool>) -> Result<Response> {
+pub async fn fortunes(request: Request<State>) -> Result<Response> {
     use crate::schema::fortune::dsl::fortune;
 
-    let connection = request.state().get()?;
-    let mut items = fortune
-        .load::<Fortune>(&connection)
-        .map_err(|error| Error::new(500, error))?;
+    let mut items = request
+        .state()
+        .run(|connection| {
+            fortune
+                .load::<Fortune>(connection)
+                .map_err(|error| Error::new(500, error))
+        })
+        .await?;
     items.push(Fortune {
         id: 0,
         message: "Additional fortune added at request time.".to_string(),
@@ -111,42 +100,37 @@ pub async fn fortunes(request: Request<Pool>) -> Result<Response> {
 }
 
 /// Update a variable number of random `World`s, returning them as a JSON list.
-pub async fn updates(request: Request<Pool>) -> Result<Response> {
-    let count = get_count_param(&request);
-    let connection = request.state().get()?;
-    let mut loaded_worlds = Vec::with_capacity(count);
-    for _index in 0..count {
-        let mut loaded_world = load_world(&connection)?;
-        loaded_world.randomnumber = random_10k();
-        loaded_worlds.push(loaded_world);
-    }
-
-    // This line is required to pass test verification otherwise we get:
-    //
-    // ```
-    // FAIL for http://tfb-server:8080/updates/20
-    //   Only 20470 executed queries in the database out of roughly 20480 expected.
-    // PASS for http://tfb-server:8080/updates/20
-    //   Rows read: 10128/10240
-    // FAIL for http://tfb-server:8080/updates/20
-    //   Only 10118 rows updated in the database out of roughly 10240 expected.
-    // ```
-    //
-    // I don't know why this is the case, but I copied it from the actix
-    // implementation and here it shall stay.
-    loaded_worlds.sort_by_key(|loaded_world| loaded_world.id);
-
-    let _ = connection.transaction::<(), Error, _>(|| {
-        for loaded_world in &loaded_worlds {
-            use crate::schema::world::dsl::{id, randomnumber, world};
-            diesel::update(world)
-                .filter(id.eq(loaded_world.id))
-                .set(randomnumber.eq(loaded_world.randomnumber))
-                .execute(&connection)
-                .map_err(|error| Error::new(500, error))?;
-        }
-        Ok(())
-    });
+pub async fn updates(request: Request<State>) -> Result<Response> {
+    let count = request.count();
+    let loaded_worlds = request
+        .state()
+        .run(move |connection| {
+            let mut loaded_worlds = Vec::with_capacity(count);
+            for _index in 0..count {
+                let mut loaded_world = load_world(connection)?;
+                loaded_world.randomnumber = random_10k();
+                loaded_worlds.push(loaded_world);
+            }
+
+            // This line is required to pass test verification otherwise we get:
+            //
+            // ```
+            // FAIL for http://tfb-server:8080/updates/20
+            //   Only 20470 executed queries in the database out of roughly 20480 expected.
+            // PASS for http://tfb-server:8080/updates/20
+            //   Rows read: 10128/10240
+            // FAIL for http://tfb-server:8080/updates/20
+            //   Only 10118 rows updated in the database out of roughly 10240 expected.
+            // ```
+            //
+            // I don't know why this is the case, but I copied it from the actix
+            // implementation and here it shall stay.
+            loaded_worlds.sort_by_key(|loaded_world| loaded_world.id);
+
+            update_worlds(connection, &loaded_worlds).map_err(|error| Error::new(500, error))?;
+            Ok(loaded_worlds)
+        })
+        .await?;
 
     Ok(Response::builder(200)
         .body(Body::from_json(&loaded_worlds)?)
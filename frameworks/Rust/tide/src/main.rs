@@ -8,10 +8,11 @@ mod models;
 mod rand;
 mod schema;
 
-use db::Pool;
+use db::State;
+use middleware::{CountParam, ServerHeader};
 
 /// Configure server routing to handlers.
-fn configure(app: &mut tide::Server<Pool>) {
+fn configure(app: &mut tide::Server<State>) {
     // 1. JSON Serialization
     app.at("/json").get(handlers::json);
 
@@ -20,21 +21,24 @@ fn configure(app: &mut tide::Server<Pool>) {
 
     // 3. Multiple Database Queries
     //
-    // AFAIK tide doesn't support optional route paramters.
-    //
-    // In the cases where the count parameter isn't given, we still want
-    // to use the same handler.
-    //
-    // Using a query parameter might be more idiomatic.
-    app.at("/queries/").get(handlers::queries);
-    app.at("/queries/:count").get(handlers::queries);
+    // AFAIK tide doesn't support optional route paramters, so the count can
+    // be given in the path, in the query string (`?queries=N`) or not at all.
+    // `CountParam` handles all three for the same handler.
+    for path in &["/queries", "/queries/", "/queries/:count"] {
+        app.at(path)
+            .with(CountParam::default())
+            .get(handlers::queries);
+    }
 
     // 4. Fortunes
     app.at("/fortunes").get(handlers::fortunes);
 
     // 5. Database Updates
-    app.at("/updates/").get(handlers::updates);
-    app.at("/updates/:count").get(handlers::updates);
+    for path in &["/updates", "/updates/", "/updates/:count"] {
+        app.at(path)
+            .with(CountParam::default())
+            .get(handlers::updates);
+    }
 
     // 6. Plaintext
     app.at("/plaintext").get(handlers::plaintext);
@@ -42,12 +46,12 @@ fn configure(app: &mut tide::Server<Pool>) {
 
 #[async_std::main]
 async fn main() -> Result<(), std::io::Error> {
-    let pool = db::connect();
-    let mut app = tide::with_state(pool);
+    let state = db::connect();
+    let mut app = tide::with_state(state);
     configure(&mut app);
 
     // The benchmarks require the 'server' header is set on every response
-    app.with(middleware::ServerHeader);
+    app.with(ServerHeader);
 
     // Uncomment these lines to enable debug logging
     // tide::log::start();
@@ -14,3 +14,74 @@ impl<State: Clone + Send + Sync + 'static> Middleware<State> for ServerHeader {
         Ok(response)
     }
 }
+
+/// The number of rows a request asks for, always in the range 1 - 500.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count(pub usize);
+
+impl Count {
+    pub const MIN: usize = 1;
+    pub const MAX: usize = 500;
+
+    /// Parse a raw count, normalizing missing or invalid values.
+    pub fn parse(raw: Option<&str>) -> Self {
+        let count = raw.and_then(|raw| raw.parse().ok()).unwrap_or(Self::MIN);
+        Count(count.clamp(Self::MIN, Self::MAX))
+    }
+}
+
+/// Middleware to read a `Count` from the request and store it as an extension.
+///
+/// The route parameter (`/queries/:count`) wins; otherwise the query string
+/// (`/queries?queries=20`) is used. Handlers read the result back through
+/// `CountExt`.
+#[derive(Debug, Clone)]
+pub struct CountParam {
+    param: &'static str,
+    query: &'static str,
+}
+
+impl CountParam {
+    /// Read the count from the route parameter `param` or query key `query`.
+    pub fn new(param: &'static str, query: &'static str) -> Self {
+        Self { param, query }
+    }
+}
+
+impl Default for CountParam {
+    /// Read `:count` from the route, or `queries` from the query string.
+    fn default() -> Self {
+        Self::new("count", "queries")
+    }
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for CountParam {
+    async fn handle(&self, mut request: Request<State>, next: Next<'_, State>) -> Result {
+        let count = match request.param(self.param) {
+            Ok(raw) if !raw.is_empty() => Count::parse(Some(raw)),
+            _ => {
+                let raw = request
+                    .url()
+                    .query_pairs()
+                    .find(|(key, _)| key == self.query)
+                    .map(|(_, value)| value);
+                Count::parse(raw.as_deref())
+            }
+        };
+        request.set_ext(count);
+        Ok(next.run(request).await)
+    }
+}
+
+/// Access to the `Count` stored by `CountParam`.
+pub trait CountExt {
+    /// Return the requested count, or the minimum if `CountParam` didn't run.
+    fn count(&self) -> usize;
+}
+
+impl<State> CountExt for Request<State> {
+    fn count(&self) -> usize {
+        self.ext::<Count>().map_or(Count::MIN, |count| count.0)
+    }
+}
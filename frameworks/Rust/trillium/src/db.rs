@@ -1,6 +1,6 @@
-use sea_orm::{ConnectOptions, Database, DatabaseConnection};
-use std::env;
-use trillium::{async_trait, Conn, Handler, Info};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+use std::{env, str::FromStr, time::Duration};
+use trillium::{async_trait, Conn, Handler, Info, Status};
 
 #[derive(Debug, Default)]
 pub struct Db(Option<DatabaseConnection>);
@@ -8,6 +8,19 @@ pub struct Db(Option<DatabaseConnection>);
 pub mod fortune;
 pub mod world;
 
+/// What sea-orm reports when sqlx's pool gives up on a checkout, as an
+/// `Exec` error for statements and a `Query` error for selects. sea-orm 0.4
+/// only keeps this text (see `src/driver/sqlx_postgres.rs`), so the
+/// dependency is pinned to the version it was taken from.
+const ACQUIRE_FAILED: &str = "Failed to acquire connection from pool.";
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
 #[async_trait]
 impl Handler for Db {
     async fn run(&self, conn: Conn) -> Conn {
@@ -18,13 +31,38 @@ impl Handler for Db {
         if self.0.is_none() {
             let db_url = env::var("DATABASE_URL").expect("env var DATABASE_URL not found");
 
-            let connect_options = ConnectOptions::new(db_url.clone());
+            let max_connections = env_or("DB_MAX_CONNECTIONS", 64);
+            let min_connections = env_or("DB_MIN_CONNECTIONS", max_connections);
+            // sqlx waits this long both to open a connection and to check one
+            // out of a full pool
+            let connect_timeout = Duration::from_millis(env_or("DB_CONNECT_TIMEOUT_MS", 5000));
+            let statement_cache: usize = env_or("DB_STATEMENT_CACHE_CAPACITY", 100);
+
+            let separator = if db_url.contains('?') { '&' } else { '?' };
+            let pool_url = format!(
+                "{}{}statement-cache-capacity={}",
+                db_url, separator, statement_cache
+            );
+
+            let mut connect_options = ConnectOptions::new(pool_url);
+            connect_options
+                .max_connections(max_connections)
+                .min_connections(min_connections)
+                .connect_timeout(connect_timeout);
 
             let db = Database::connect(connect_options)
                 .await
                 .map_err(|e| format!("could not connect to {}: {}", &db_url, e))
                 .unwrap();
 
+            log::info!(
+                "db pool: {}..={} connections, {:?} connect timeout, {} cached statements",
+                min_connections,
+                max_connections,
+                connect_timeout,
+                statement_cache
+            );
+
             self.0 = Some(db);
         }
     }
@@ -32,10 +70,38 @@ impl Handler for Db {
 
 pub trait DbConnExt {
     fn db(&self) -> &DatabaseConnection;
+
+    /// Halts with 503 when the pool had no connection to spare in time, or
+    /// with 500 for any other database error.
+    fn with_db_error(self, error: DbErr) -> Self;
 }
 
 impl DbConnExt for Conn {
     fn db(&self) -> &DatabaseConnection {
         self.state().unwrap()
     }
+
+    fn with_db_error(self, error: DbErr) -> Self {
+        let status = match &error {
+            DbErr::Conn(_) => Status::ServiceUnavailable,
+            DbErr::Exec(message) | DbErr::Query(message) if message == ACQUIRE_FAILED => {
+                Status::ServiceUnavailable
+            }
+            _ => Status::InternalServerError,
+        };
+        log::error!("database error: {}", error);
+        self.with_status(status).halt()
+    }
+}
+
+/// Like `trillium::conn_try!`, but maps the error through
+/// `DbConnExt::with_db_error`.
+#[macro_export]
+macro_rules! db_try {
+    ($expr:expr, $conn:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(error) => return $crate::db::DbConnExt::with_db_error($conn, error),
+        }
+    };
 }
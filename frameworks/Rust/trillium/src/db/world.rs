@@ -37,3 +37,13 @@ impl TryFrom<ActiveModel> for Model {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+/// A random world id, in `1..=10_000`.
+pub fn random_id() -> i32 {
+    fastrand::i32(1..=10_000)
+}
+
+/// A new value for a world's `randomnumber`, also in `1..=10_000`.
+pub fn random_number() -> i32 {
+    fastrand::i32(1..=10_000)
+}
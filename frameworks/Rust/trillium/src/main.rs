@@ -5,5 +5,9 @@ mod routes;
 use application::application;
 
 fn main() {
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("warn,trillium_techempower=info"),
+    )
+    .init();
     trillium_async_std::run(application())
 }
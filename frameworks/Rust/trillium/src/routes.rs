@@ -4,17 +4,26 @@ mod fortune;
 mod json;
 mod plaintext;
 mod queries;
+mod queries_param;
 mod updates;
 
+use queries_param::QueriesParam;
+
 pub fn router() -> Router {
     Router::build(|mut router| {
         router.get("/fortunes", fortune::handler);
         router.get("/json", json::handler);
-        router.get("/queries/:queries", queries::handler);
-        router.get("/queries", queries::handler);
+        router.get(
+            "/queries/:queries",
+            (QueriesParam::new("queries"), queries::handler),
+        );
+        router.get("/queries", (QueriesParam::new("queries"), queries::handler));
         router.get("/db", db::handler);
         router.get("/plaintext", plaintext::handler);
-        router.get("/updates/:updates", updates::handler);
-        router.get("/updates", updates::handler);
+        router.get(
+            "/updates/:updates",
+            (QueriesParam::new("updates"), updates::handler),
+        );
+        router.get("/updates", (QueriesParam::new("updates"), updates::handler));
     })
 }
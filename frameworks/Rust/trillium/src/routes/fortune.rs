@@ -2,8 +2,9 @@ use crate::db::{
     fortune::{Entity as Fortunes, Model as Fortune},
     DbConnExt,
 };
+use crate::db_try;
 use sea_orm::entity::prelude::*;
-use trillium::{conn_try, Conn, KnownHeaderName::ContentType};
+use trillium::{Conn, KnownHeaderName::ContentType};
 use trillium_askama::{AskamaConnExt, Template};
 
 #[derive(Template)]
@@ -15,7 +16,7 @@ struct FortuneTemplate<'a> {
 pub async fn handler(conn: Conn) -> Conn {
     let db = conn.db();
 
-    let mut fortunes = conn_try!(Fortunes::find().all(db).await, conn);
+    let mut fortunes = db_try!(Fortunes::find().all(db).await, conn);
     fortunes.push(Fortune {
         id: 0,
         message: String::from("Additional fortune added at request time."),
@@ -1,24 +1,23 @@
-use crate::db::{world::Entity as Worlds, DbConnExt};
+use crate::db::{
+    world::{random_id, Entity as Worlds},
+    DbConnExt,
+};
+use crate::db_try;
+use crate::routes::queries_param::QueriesConnExt;
 use futures_lite::StreamExt;
 use futures_util::stream::futures_unordered::FuturesUnordered;
 use sea_orm::entity::prelude::*;
 use std::iter;
-use trillium::{conn_try, Conn};
+use trillium::Conn;
 use trillium_api::ApiConnExt;
-use trillium_router::RouterConnExt;
 
 pub async fn handler(conn: Conn) -> Conn {
-    let queries = conn
-        .param("queries")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(1)
-        .min(500)
-        .max(1);
+    let queries = conn.queries();
 
     let db = conn.db();
 
     let vec_of_worlds: Result<Vec<_>, DbErr> =
-        iter::repeat_with(|| Worlds::find_by_id(fastrand::i32(1..10000)).one(db))
+        iter::repeat_with(|| Worlds::find_by_id(random_id()).one(db))
             .take(queries)
             .collect::<FuturesUnordered<_>>()
             .map(|x| match x {
@@ -28,7 +27,7 @@ pub async fn handler(conn: Conn) -> Conn {
             .try_collect()
             .await;
 
-    let vec_of_worlds = conn_try!(vec_of_worlds, conn);
+    let vec_of_worlds = db_try!(vec_of_worlds, conn);
 
     conn.with_json(&vec_of_worlds)
 }
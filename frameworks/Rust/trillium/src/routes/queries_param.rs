@@ -0,0 +1,47 @@
+use trillium::{async_trait, Conn, Handler};
+use trillium_router::RouterConnExt;
+
+/// How many worlds a request asked for, clamped to `1..=500`.
+#[derive(Clone, Copy, Debug)]
+struct Queries(usize);
+
+/// Reads the query count from the named path segment, falling back to
+/// `?queries=` or `?q=`. Must run after the router has matched the route.
+#[derive(Clone, Copy, Debug)]
+pub struct QueriesParam(&'static str);
+
+impl QueriesParam {
+    pub fn new(path_param: &'static str) -> Self {
+        Self(path_param)
+    }
+}
+
+#[async_trait]
+impl Handler for QueriesParam {
+    async fn run(&self, conn: Conn) -> Conn {
+        let queries = conn
+            .param(self.0)
+            .or_else(|| {
+                conn.querystring().split('&').find_map(|pair| {
+                    pair.split_once('=')
+                        .filter(|(key, _)| *key == "queries" || *key == "q")
+                        .map(|(_, value)| value)
+                })
+            })
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1)
+            .clamp(1, 500);
+
+        conn.with_state(Queries(queries))
+    }
+}
+
+pub trait QueriesConnExt {
+    fn queries(&self) -> usize;
+}
+
+impl QueriesConnExt for Conn {
+    fn queries(&self) -> usize {
+        self.state().map_or(1, |&Queries(queries)| queries)
+    }
+}
@@ -1,36 +1,70 @@
 use crate::db::{
-    world::{Entity as Worlds, Model as World},
+    world::{random_id, random_number, Entity as Worlds, Model as World},
     DbConnExt,
 };
+use crate::db_try;
+use crate::routes::queries_param::QueriesConnExt;
 
-use futures_util::stream::{futures_unordered::FuturesUnordered, StreamExt};
-use sea_orm::{entity::prelude::*, IntoActiveModel, Set};
+use futures_lite::StreamExt;
+use futures_util::stream::futures_unordered::FuturesUnordered;
+use sea_orm::{entity::prelude::*, ConnectionTrait, DatabaseConnection, Statement, Value};
+use std::fmt::Write;
 use trillium::Conn;
 use trillium_api::ApiConnExt;
-use trillium_router::RouterConnExt;
 
 pub async fn handler(conn: Conn) -> Conn {
-    let queries = conn
-        .param("updates")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(1)
-        .min(500)
-        .max(1);
+    let queries = conn.queries();
 
     let db = conn.db();
 
-    let vec_of_worlds: Vec<World> =
-        std::iter::repeat_with(|| Worlds::find_by_id(fastrand::i32(1..10000)).one(db))
+    let vec_of_worlds: Result<Vec<World>, DbErr> =
+        std::iter::repeat_with(|| Worlds::find_by_id(random_id()).one(db))
             .take(queries)
             .collect::<FuturesUnordered<_>>()
-            .filter_map(|x| async move { x.ok().flatten() })
-            .filter_map(|w| async move {
-                let mut am = w.clone().into_active_model();
-                am.random_number = Set(fastrand::i32(1..10000));
-                am.update(db).await.ok().and_then(|a| a.try_into().ok())
+            .map(|x| match x {
+                Ok(Some(world)) => Ok(world),
+                Ok(None) => Err(DbErr::RecordNotFound(String::from("not found"))),
+                Err(error) => Err(error),
             })
-            .collect()
+            .try_collect()
             .await;
 
+    let mut vec_of_worlds = db_try!(vec_of_worlds, conn);
+    for world in &mut vec_of_worlds {
+        world.random_number = random_number();
+    }
+    // lock rows in the same order across concurrent updates
+    vec_of_worlds.sort_by_key(|world| world.id);
+
+    db_try!(update_all(db, &vec_of_worlds).await, conn);
+
     conn.with_json(&vec_of_worlds)
 }
+
+/// Writes every world back in a single statement, joining against a
+/// `VALUES` list of (id, randomnumber) pairs. `worlds` must be sorted by id.
+async fn update_all(db: &DatabaseConnection, worlds: &[World]) -> Result<(), DbErr> {
+    // an id drawn twice may only appear once in VALUES, or postgres picks
+    // either row's number
+    let mut changes: Vec<(i32, i32)> = worlds
+        .iter()
+        .map(|world| (world.id, world.random_number))
+        .collect();
+    changes.dedup_by_key(|&mut (id, _)| id);
+
+    let mut sql = String::from(r#"UPDATE "World" SET randomnumber = v.randomnumber FROM (VALUES "#);
+    let mut values = Vec::<Value>::with_capacity(changes.len() * 2);
+    for (i, (id, random_number)) in changes.into_iter().enumerate() {
+        if i > 0 {
+            sql.push_str(", ");
+        }
+        write!(sql, "(${}::int, ${}::int)", 2 * i + 1, 2 * i + 2).unwrap();
+        values.push(id.into());
+        values.push(random_number.into());
+    }
+    sql.push_str(r#") AS v (id, randomnumber) WHERE "World".id = v.id"#);
+
+    let statement = Statement::from_sql_and_values(db.get_database_backend(), &sql, values);
+    db.execute(statement).await?;
+    Ok(())
+}
@@ -1,5 +1,6 @@
+use arc_swap::ArcSwap;
 use futures::stream::futures_unordered::FuturesUnordered;
-use futures::{FutureExt, StreamExt};
+use futures::{TryFutureExt, TryStreamExt};
 use rand::distributions::{Distribution, Uniform};
 use rand::rngs::SmallRng;
 use rand::SeedableRng;
@@ -7,7 +8,13 @@ use serde::Serialize;
 use sqlx::postgres::PgPool;
 use sqlx::FromRow;
 use std::cell::RefCell;
-use warp::http::header;
+use std::convert::Infallible;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use warp::http::{header, StatusCode};
+use warp::reject::{MethodNotAllowed, Reject};
 use warp::{Filter, Rejection, Reply};
 use yarte::Template;
 
@@ -47,20 +54,88 @@ pub struct World {
 }
 
 impl World {
-    async fn get_by_id(pool: &PgPool, id: i32) -> Self {
+    async fn get_by_id(pool: &PgPool, id: i32) -> Result<Self, sqlx::Error> {
         sqlx::query_as("SELECT id, randomnumber FROM world WHERE id=$1")
             .bind(id)
             .fetch_one(pool)
             .await
-            .unwrap()
     }
+
+    /// Writes back every world's `randomnumber` in one `UNNEST` statement.
+    /// `worlds` must be sorted by id; only the first of any repeated id is
+    /// written.
+    async fn update_all(pool: &PgPool, worlds: &[Self]) -> Result<(), sqlx::Error> {
+        let mut changes: Vec<(i32, i32)> = worlds
+            .iter()
+            .map(|world| (world.id, world.randomnumber))
+            .collect();
+        changes.dedup_by_key(|&mut (id, _)| id);
+        let (ids, numbers): (Vec<i32>, Vec<i32>) = changes.into_iter().unzip();
+        sqlx::query(
+            "UPDATE world SET randomnumber = v.randomnumber \
+             FROM UNNEST($1::int[], $2::int[]) AS v (id, randomnumber) \
+             WHERE world.id = v.id",
+        )
+        .bind(ids)
+        .bind(numbers)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// A failed database query, turned into a JSON error body by `handle_rejection`.
+#[derive(Debug)]
+struct DbError(sqlx::Error);
+
+impl Reject for DbError {}
+
+impl DbError {
+    fn status(&self) -> StatusCode {
+        match self.0 {
+            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+            sqlx::Error::PoolTimedOut => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+fn db_error(error: sqlx::Error) -> Rejection {
+    warp::reject::custom(DbError(error))
+}
+
+#[derive(Serialize)]
+struct ErrorMessage {
+    code: u16,
+    message: &'static str,
+}
+
+async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let status = if let Some(error) = rejection.find::<DbError>() {
+        error.status()
+    } else if rejection.is_not_found() {
+        StatusCode::NOT_FOUND
+    } else if rejection.find::<MethodNotAllowed>().is_some() {
+        StatusCode::METHOD_NOT_ALLOWED
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let message = ErrorMessage {
+        code: status.as_u16(),
+        message: status.canonical_reason().unwrap_or("Unknown error"),
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&message),
+        status,
+    ))
 }
 
 fn db(pool: &'static PgPool) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     let between = Uniform::from(1..=10_000);
     warp::path!("db").and_then(move || async move {
         let id = with_rng(|rng| between.sample(rng));
-        Ok::<_, Rejection>(warp::reply::json(&World::get_by_id(pool, id).await))
+        let world = World::get_by_id(pool, id).await.map_err(db_error)?;
+        Ok::<_, Rejection>(warp::reply::json(&world))
     })
 }
 
@@ -74,8 +149,9 @@ fn queries(pool: &'static PgPool) -> impl Filter<Extract = impl Reply, Error = R
                 (0..queries)
                     .map(|_| World::get_by_id(pool, between.sample(rng)))
                     .collect::<FuturesUnordered<_>>()
-                    .collect::<Vec<_>>()
-                    .map(|worlds| Ok::<_, Rejection>(warp::reply::json(&worlds)))
+                    .try_collect::<Vec<_>>()
+                    .map_ok(|worlds| warp::reply::json(&worlds))
+                    .map_err(db_error)
             })
         })
 }
@@ -97,7 +173,7 @@ fn fortune(pool: &'static PgPool) -> impl Filter<Extract = impl Reply, Error = R
         let mut fortunes = sqlx::query_as("SELECT id, message FROM fortune")
             .fetch_all(pool)
             .await
-            .unwrap();
+            .map_err(db_error)?;
         fortunes.push(Fortune {
             id: 0,
             message: "Additional fortune added at request time.".into(),
@@ -119,41 +195,107 @@ fn update(pool: &'static PgPool) -> impl Filter<Extract = impl Reply, Error = Re
                 (0..queries)
                     .map(|_| World::get_by_id(pool, between.sample(rng)))
                     .collect::<FuturesUnordered<_>>()
-                    .collect::<Vec<_>>()
+                    .try_collect::<Vec<_>>()
             })
-            .await;
+            .await
+            .map_err(db_error)?;
             with_rng(|rng| {
                 for world in &mut worlds {
                     world.randomnumber = between.sample(rng);
                 }
             });
-            let mut transaction = pool.begin().await.unwrap();
-            for world in &worlds {
-                sqlx::query("UPDATE world SET randomnumber = $1 WHERE id = $2")
-                    .bind(world.randomnumber)
-                    .bind(world.id)
-                    .execute(&mut transaction)
-                    .await
-                    .unwrap();
+            // Concurrent batches touching the same rows must lock them in
+            // the same order, or they can deadlock each other.
+            worlds.sort_unstable_by_key(|world| world.id);
+            // a repeated id reports the number update_all actually writes
+            for i in 1..worlds.len() {
+                if worlds[i].id == worlds[i - 1].id {
+                    worlds[i].randomnumber = worlds[i - 1].randomnumber;
+                }
             }
-            transaction.commit().await.unwrap();
+            World::update_all(pool, &worlds).await.map_err(db_error)?;
             Ok::<_, Rejection>(warp::reply::json(&worlds))
         })
 }
 
-fn routes(pool: &'static PgPool) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+/// Every `World` row, loaded with a single query. Requests read a snapshot
+/// without locking; a refresh swaps in a whole new table.
+type WorldCache = ArcSwap<Vec<World>>;
+
+async fn load_worlds(pool: &PgPool) -> Result<Vec<World>, sqlx::Error> {
+    sqlx::query_as("SELECT id, randomnumber FROM world")
+        .fetch_all(pool)
+        .await
+}
+
+/// How often to reload the cache, from `CACHED_QUERIES_REFRESH_SECS`. Unset or
+/// zero keeps the table loaded at startup for the life of the server.
+fn cache_refresh_interval() -> Option<Duration> {
+    env::var("CACHED_QUERIES_REFRESH_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+}
+
+fn refresh_cache(pool: &'static PgPool, cache: &'static WorldCache, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+        // The first tick completes immediately, and the table is already fresh.
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // On failure keep serving the old table and try again next tick.
+            if let Ok(worlds) = load_worlds(pool).await {
+                cache.store(Arc::new(worlds));
+            }
+        }
+    });
+}
+
+fn cached_queries(
+    cache: &'static WorldCache,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let clamped = warp::path!(u32).map(|queries: u32| queries.clamp(1, 500));
+    warp::path!("cached-queries" / ..)
+        .and(clamped.or(warp::any().map(|| 1)).unify())
+        .map(move |queries| {
+            let worlds = cache.load();
+            let between = Uniform::from(0..worlds.len());
+            let picked: Vec<&World> =
+                with_rng(|rng| (0..queries).map(|_| &worlds[between.sample(rng)]).collect());
+            warp::reply::json(&picked)
+        })
+}
+
+fn routes(
+    pool: &'static PgPool,
+    cache: &'static WorldCache,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
     json()
         .or(plaintext())
         .or(db(pool))
         .or(queries(pool))
         .or(fortune(pool))
         .or(update(pool))
+        .or(cached_queries(cache))
+        .recover(handle_rejection)
         .map(|reply| warp::reply::with_header(reply, header::SERVER, "warp"))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), sqlx::Error> {
-    let pool = Box::leak(Box::new(PgPool::connect(DATABASE_URL).await?));
-    warp::serve(routes(pool)).run(([0, 0, 0, 0], 8080)).await;
+    let pool: &'static PgPool = Box::leak(Box::new(PgPool::connect(DATABASE_URL).await?));
+    let worlds = load_worlds(pool).await?;
+    if worlds.is_empty() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    let cache: &'static WorldCache = Box::leak(Box::new(ArcSwap::from_pointee(worlds)));
+    if let Some(every) = cache_refresh_interval() {
+        refresh_cache(pool, cache, every);
+    }
+    warp::serve(routes(pool, cache))
+        .run(([0, 0, 0, 0], 8080))
+        .await;
     Ok(())
 }
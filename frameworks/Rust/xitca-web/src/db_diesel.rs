@@ -1,6 +1,9 @@
 use std::{error::Error, fmt, future::Future, io, time::Duration};
 
-use diesel::prelude::*;
+use diesel::{
+    prelude::*,
+    sql_types::{Array, Integer},
+};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use tang_rs::{Manager, ManagerFuture, ManagerTimeout, Pool};
 use tokio::{
@@ -161,15 +164,7 @@ impl DieselPool {
 
             worlds.sort_by_key(|w| w.id);
 
-            c.transaction::<_, diesel::result::Error, _>(|| {
-                for w in &worlds {
-                    diesel::update(world)
-                        .filter(id.eq(w.id))
-                        .set(randomnumber.eq(w.randomnumber))
-                        .execute(c)?;
-                }
-                Ok(())
-            })?;
+            update_worlds(c, &worlds)?;
 
             Ok(worlds)
         })
@@ -194,3 +189,47 @@ impl DieselPool {
         .await?
     }
 }
+
+fn update_worlds(c: &PgConnection, worlds: &[World]) -> QueryResult<()> {
+    let mut changes = worlds
+        .iter()
+        .map(|w| (w.id, w.randomnumber))
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|&(id, _)| id);
+    changes.dedup_by_key(|&mut (id, _)| id);
+
+    if cfg!(feature = "per-row-updates") {
+        update_worlds_per_row(c, &changes)
+    } else {
+        update_worlds_batch(c, &changes)
+    }
+}
+
+fn update_worlds_batch(c: &PgConnection, changes: &[(i32, i32)]) -> QueryResult<()> {
+    let (ids, numbers): (Vec<i32>, Vec<i32>) = changes.iter().copied().unzip();
+
+    diesel::sql_query(
+        "UPDATE world SET randomnumber = v.randomnumber \
+         FROM UNNEST($1::int[], $2::int[]) AS v (id, randomnumber) \
+         WHERE world.id = v.id",
+    )
+    .bind::<Array<Integer>, _>(ids)
+    .bind::<Array<Integer>, _>(numbers)
+    .execute(c)?;
+
+    Ok(())
+}
+
+fn update_worlds_per_row(c: &PgConnection, changes: &[(i32, i32)]) -> QueryResult<()> {
+    use crate::schema::world::dsl::*;
+
+    c.transaction(|| {
+        for &(wid, number) in changes {
+            diesel::update(world)
+                .filter(id.eq(wid))
+                .set(randomnumber.eq(number))
+                .execute(c)?;
+        }
+        Ok(())
+    })
+}